@@ -3,6 +3,7 @@ use proc_macro::TokenStream;
 use quote::quote;
 use syn::{Ident, parse_macro_input, spanned::Spanned};
 
+mod model;
 mod parse;
 mod pdf;
 mod translation;
@@ -39,60 +40,132 @@ pub fn define_model(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let value_fn = &pdf_input.distribution;
     let likelihood_fn = &pdf_input.likelihood;
     let norm_fn = &pdf_input.norm;
+    let helpers = &pdf_input.helpers;
+    // Functions inlined into the graphs, the model's own helpers shadowing
+    // the shape library.
+    let inlined: Vec<syn::ItemFn> = match parse::library_shapes() {
+        Ok(library) => helpers.iter().cloned().chain(library).collect(),
+        Err(e) => return e.to_compile_error().into(),
+    };
 
-    let mut value = match parse::build_graph(pdf_struct, value_fn) {
+    let (mut value, value_output) = match parse::build_graph(pdf_struct, value_fn, &inlined) {
         Ok(e) => e,
-        Err(e) => {
-            return syn::Error::new_spanned(value_fn, e.to_string())
-                .to_compile_error()
-                .into();
-        }
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let fields = match parse::verify_types(pdf_struct, value_fn) {
+        Ok(fields) => fields,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let yield_field = match parse::yield_field(pdf_struct) {
+        Ok(field) => field,
+        Err(e) => return e.to_compile_error().into(),
     };
 
     let res = translation::translate(
         &value,
-        value.len() - 1,
+        value_output,
         Ident::new("_value_and_gradient", value_fn.span()),
     );
 
-    let likelihood = match likelihood_fn {
-        Some(f) => match parse::build_graph(pdf_struct, f) {
-            Ok(e) => translation::translate(
-                &e,
-                e.len() - 1,
-                Ident::new("_likelihood", likelihood_fn.span()),
-            ),
-            Err(e) => {
-                return syn::Error::new_spanned(value_fn, e.to_string())
-                    .to_compile_error()
-                    .into();
-            }
+    let (mut likelihood_graph, mut likelihood_output) = match likelihood_fn {
+        Some(f) => match parse::build_graph(pdf_struct, f, &inlined) {
+            Ok(e) => e,
+            Err(e) => return e.to_compile_error().into(),
         },
         None => {
-            let l = Node::new_builtin(Builtin::Log, value.len() - 1);
+            let l = Node::new_builtin(Builtin::Log, value_output);
             let index = value.insert(l);
-            translation::translate(
-                &value,
-                index,
-                Ident::new("_likelihood", likelihood_fn.span()),
+            (value, index)
+        }
+    };
+
+    // A user-supplied `norm` is differentiated with the likelihood and its
+    // logarithm subtracted from it.
+    let (normalization, normalization_fn) = match norm_fn {
+        Some(f) => {
+            let (norm_graph, norm_output) = match parse::build_norm_graph(pdf_struct, f, &inlined) {
+                Ok(e) => e,
+                Err(e) => return e.to_compile_error().into(),
+            };
+            let norm_in_likelihood =
+                match parse::extend_graph(&mut likelihood_graph, pdf_struct, f, &inlined) {
+                    Ok(id) => id,
+                    Err(e) => return e.to_compile_error().into(),
+                };
+            let log_norm = likelihood_graph.builtin(Builtin::Log, norm_in_likelihood);
+            likelihood_output = likelihood_graph.sub(likelihood_output, log_norm);
+            (
+                model::Normalization::Function,
+                translation::translate(
+                    &norm_graph,
+                    norm_output,
+                    Ident::new("_normalization", f.sig.ident.span()),
+                ),
             )
         }
+        None => match analytic_normalization(pdf_struct, value_fn, &inlined) {
+            Some(antiderivative) => (model::Normalization::Analytic, antiderivative),
+            None => (model::Normalization::Numeric, quote! {}),
+        },
     };
 
-    let output = quote! {
+    let model_impl =
+        model::implement_model(pdf_struct, &fields, yield_field.as_ref(), &normalization);
+    let mut emitted_struct = pdf_struct.clone();
+    parse::strip_yield_attribute(&mut emitted_struct);
+
+    let likelihood = translation::translate(
+        &likelihood_graph,
+        likelihood_output,
+        Ident::new("_likelihood", likelihood_fn.span()),
+    );
+    let likelihood_hessian = translation::translate_hessian(
+        &mut likelihood_graph,
+        likelihood_output,
+        Ident::new("_likelihood_hessian", likelihood_fn.span()),
+    );
 
-        use intermediate_representation::{Float, FloatConsts};
+    let output = quote! {
         mod #model_name {
             use super::*;
+            use intermediate_representation::{Float, FloatConsts};
+            use intermediate_representation::special::*;
+            use intermediate_representation::shapes::*;
 
-            #pdf_struct
+            #emitted_struct
             #value_fn
             #norm_fn
             #likelihood_fn
+            #(#helpers)*
             #likelihood
+            #likelihood_hessian
             #res
+            #normalization_fn
+            #model_impl
         }
     };
 
     output.into()
 }
+
+/// Generates `_antiderivative` of the distribution in every `Data` field when
+/// the integration pass succeeds for each of them in turn.
+fn analytic_normalization(
+    pdf_struct: &syn::ItemStruct,
+    value_fn: &syn::ItemFn,
+    helpers: &[syn::ItemFn],
+) -> Option<proc_macro2::TokenStream> {
+    let (mut graph, mut output) = parse::build_graph(pdf_struct, value_fn, helpers).ok()?;
+    let data: Vec<usize> = (0..graph.len())
+        .filter(|&id| matches!(graph.get_node(id), Node::Variable(v) if v.fixed))
+        .collect();
+    for variable in data {
+        output = graph.antiderivative(output, variable)?;
+    }
+    Some(translation::translate(
+        &graph,
+        output,
+        Ident::new("_antiderivative", value_fn.sig.ident.span()),
+    ))
+}
@@ -0,0 +1,173 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Ident, ItemStruct, Type, TypePath};
+
+/// How the generated model normalizes its distribution.
+pub enum Normalization {
+    /// Adaptive integration over the ranges of the `Data` fields at runtime.
+    Numeric,
+    /// The generated `_antiderivative` evaluated at the corners of the
+    /// ranges, falling back to numeric integration where it is not finite.
+    Analytic,
+    /// The user's `norm`, generated as `_normalization` and already folded
+    /// into `_likelihood`.
+    Function,
+}
+
+/// Implements `fitting::model::Model` for the PDF struct, naming the entries
+/// of the generated `parameters` and `data` arrays in declaration order.
+pub fn implement_model(
+    pdf_struct: &ItemStruct,
+    fields: &[(Ident, Type)],
+    yield_field: Option<&Ident>,
+    normalization: &Normalization,
+) -> TokenStream {
+    let mut parameters = Vec::new();
+    let mut data = Vec::new();
+    for (ident, ty) in fields {
+        if let Type::Path(TypePath { path, .. }) = ty {
+            if path.segments.last().unwrap().ident == "Parameter" {
+                parameters.push(ident);
+            } else {
+                data.push(ident);
+            }
+        }
+    }
+
+    let struct_name = &pdf_struct.ident;
+    let parameter_names: Vec<String> = parameters.iter().map(|i| i.to_string()).collect();
+    let data_names: Vec<String> = data.iter().map(|i| i.to_string()).collect();
+    let parameter_count = parameters.len();
+    let yield_index = match yield_field.and_then(|y| parameters.iter().position(|p| *p == y)) {
+        Some(index) => quote! { Some(#index) },
+        None => quote! { None },
+    };
+    let data_count = data.len();
+    let distribution = match normalization {
+        Normalization::Numeric | Normalization::Analytic => quote! {
+            fn distribution(&self, parameters: &[Float], event: &[Float], gradient: &mut [Float]) -> Float {
+                let (value, g) = _value_and_gradient(
+                    parameters.try_into().expect("parameter count does not match the model"),
+                    event.try_into().expect("data count does not match the model"),
+                );
+                gradient.copy_from_slice(&g);
+                value
+            }
+        },
+        Normalization::Function => quote! {
+            fn distribution(&self, parameters: &[Float], event: &[Float], gradient: &mut [Float]) -> Float {
+                let parameters = parameters.try_into().expect("parameter count does not match the model");
+                let event = event.try_into().expect("data count does not match the model");
+                let (value, g) = _value_and_gradient(parameters, event);
+                let (norm, norm_gradient) = _normalization(parameters, event);
+                let normalized = value / norm;
+                for ((gradient, g), n) in gradient.iter_mut().zip(g).zip(norm_gradient) {
+                    *gradient = (g - normalized * n) / norm;
+                }
+                normalized
+            }
+        },
+    };
+    let numeric = quote! {
+        ::fitting::integration::log_integral(
+            ::fitting::integration::integrate_box(
+                &ranges,
+                parameters.len(),
+                ::fitting::integration::TOLERANCE,
+                &mut |event, g| self.distribution(parameters, event, g),
+            ),
+            gradient,
+        )
+    };
+    let log_normalization = match normalization {
+        Normalization::Numeric => quote! {
+            fn log_normalization(&self, parameters: &[Float], gradient: &mut [Float]) -> Float {
+                let ranges = [#(self.#data.distribution().bounds()),*];
+                #numeric
+            }
+        },
+        Normalization::Analytic => quote! {
+            fn log_normalization(&self, parameters: &[Float], gradient: &mut [Float]) -> Float {
+                let ranges = [#(self.#data.distribution().bounds()),*];
+                let (integral, integral_gradient) = ::fitting::integration::antiderivative_box(
+                    &ranges,
+                    parameters.len(),
+                    &mut |corner, g| {
+                        let (value, d) = _antiderivative(
+                            parameters.try_into().expect("parameter count does not match the model"),
+                            corner.try_into().expect("data count does not match the model"),
+                        );
+                        g.copy_from_slice(&d);
+                        value
+                    },
+                );
+                if integral > 0.0
+                    && integral.is_finite()
+                    && integral_gradient.iter().all(|d| d.is_finite())
+                {
+                    return ::fitting::integration::log_integral((integral, integral_gradient), gradient);
+                }
+                #numeric
+            }
+        },
+        Normalization::Function => quote! {},
+    };
+
+    quote! {
+        impl #struct_name {
+            pub const PARAMETERS: [&'static str; #parameter_count] = [#(#parameter_names),*];
+            pub const DATA: [&'static str; #data_count] = [#(#data_names),*];
+        }
+
+        impl ::fitting::model::Model for #struct_name {
+            fn parameter_names(&self) -> Vec<String> {
+                Self::PARAMETERS.iter().map(|name| name.to_string()).collect()
+            }
+
+            fn data_names(&self) -> Vec<String> {
+                Self::DATA.iter().map(|name| name.to_string()).collect()
+            }
+
+            fn parameters(&self) -> Vec<::fitting::parameter::Parameter> {
+                vec![#(self.#parameters.clone()),*]
+            }
+
+            fn data(&self) -> Vec<::fitting::data::Data> {
+                vec![#(self.#data.clone()),*]
+            }
+
+            fn yield_index(&self) -> Option<usize> {
+                #yield_index
+            }
+
+            #distribution
+
+            fn log_likelihood(&self, parameters: &[Float], event: &[Float], gradient: &mut [Float]) -> Float {
+                let (value, g) = _likelihood(
+                    parameters.try_into().expect("parameter count does not match the model"),
+                    event.try_into().expect("data count does not match the model"),
+                );
+                gradient.copy_from_slice(&g);
+                value
+            }
+
+            fn log_likelihood_hessian(
+                &self,
+                parameters: &[Float],
+                event: &[Float],
+                gradient: &mut [Float],
+                hessian: &mut [Float],
+            ) -> Option<Float> {
+                let (value, g, h) = _likelihood_hessian(
+                    parameters.try_into().expect("parameter count does not match the model"),
+                    event.try_into().expect("data count does not match the model"),
+                );
+                gradient.copy_from_slice(&g);
+                hessian.copy_from_slice(h.as_flattened());
+                Some(value)
+            }
+
+            #log_normalization
+        }
+    }
+}
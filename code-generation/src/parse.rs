@@ -3,16 +3,19 @@ use intermediate_representation::{
     binary_operation::BinaryOperation,
     builtin::Builtin,
     expression::{ExpressionGraph, Node, NodeId},
+    shapes,
 };
 
+use quote::ToTokens;
 use std::collections::HashMap;
 use syn::{
-    Error, Expr, ExprPath, Fields, FnArg, Ident, ItemFn, ItemStruct, Pat, PatIdent, Result, Stmt,
-    Type, TypePath, spanned::Spanned,
+    Error, Expr, ExprPath, Field, Fields, FnArg, Ident, Item, ItemFn, ItemStruct, Pat, PatIdent,
+    Result, Stmt, Type, TypePath, spanned::Spanned,
 };
 
 fn build_node(
     graph: &mut ExpressionGraph,
+    helpers: &[&ItemFn],
     node_map: &HashMap<Ident, NodeId>,
     expr: &Expr,
 ) -> Result<Node> {
@@ -40,9 +43,9 @@ fn build_node(
 
     match expr {
         Expr::Binary(expr_bin) => {
-            let left_node = build_node(graph, node_map, &expr_bin.left)?;
+            let left_node = build_node(graph, helpers, node_map, &expr_bin.left)?;
             let left = graph.insert(left_node);
-            let right_node = build_node(graph, node_map, &expr_bin.right)?;
+            let right_node = build_node(graph, helpers, node_map, &expr_bin.right)?;
             let right = graph.insert(right_node);
 
             // let right = { graph.insert({ build_node(graph, node_map, &expr_bin.right)? }) };
@@ -57,6 +60,14 @@ fn build_node(
                 syn::BinOp::Sub(_) => BinaryOperation::Sub,
                 syn::BinOp::Mul(_) => BinaryOperation::Mul,
                 syn::BinOp::Div(_) => BinaryOperation::Div,
+                syn::BinOp::Lt(_) => BinaryOperation::Lt,
+                syn::BinOp::Le(_) => BinaryOperation::Le,
+                syn::BinOp::Gt(_) => BinaryOperation::Gt,
+                syn::BinOp::Ge(_) => BinaryOperation::Ge,
+                syn::BinOp::Eq(_) => BinaryOperation::Eq,
+                syn::BinOp::Ne(_) => BinaryOperation::Ne,
+                syn::BinOp::And(_) => BinaryOperation::And,
+                syn::BinOp::Or(_) => BinaryOperation::Or,
                 _ => {
                     return Err(syn::Error::new_spanned(
                         &expr_bin.op,
@@ -69,7 +80,14 @@ fn build_node(
         Expr::Path(ExprPath { path, .. }) => {
             let segments: Vec<_> = path.segments.iter().collect();
             if segments.len() == 1 {
-                Ok(graph.get_node(*node_map.get(&path.segments[0].ident).unwrap()))
+                let ident = &path.segments[0].ident;
+                match node_map.get(ident) {
+                    Some(&id) => Ok(graph.get_node(id)),
+                    None => Err(Error::new_spanned(
+                        path,
+                        format!("unknown identifier `{}`", ident),
+                    )),
+                }
             } else if segments.len() == 2 && segments[0].ident == "Float" {
                 match segments[1].ident.to_string().as_str() {
                     "PI" => return Ok(Node::new_float(Float::PI)),
@@ -93,10 +111,10 @@ fn build_node(
             syn::Lit::Int(i) => Ok(Node::new_integer(i.base10_parse::<i32>()?)),
             _ => Err(syn::Error::new_spanned(lit, "Unsupported literal")),
         },
-        Expr::Paren(inner) => build_node(graph, node_map, &inner.expr),
+        Expr::Paren(inner) => build_node(graph, helpers, node_map, &inner.expr),
         Expr::Unary(expr_unary) => {
             if let syn::UnOp::Neg(_) = expr_unary.op {
-                let inner_node = build_node(graph, node_map, &expr_unary.expr)?;
+                let inner_node = build_node(graph, helpers, node_map, &expr_unary.expr)?;
                 if let Node::Constant(value) = inner_node {
                     let negative = Node::Constant(value.negate());
                     graph.insert(negative.clone());
@@ -108,13 +126,19 @@ fn build_node(
                 ));
                 return Ok(Node::BinaryOperation(BinaryOperation::Sub, zero, index));
             }
+            if let syn::UnOp::Not(_) = expr_unary.op {
+                let inner_node = build_node(graph, helpers, node_map, &expr_unary.expr)?;
+                let index = graph.insert(inner_node);
+                let zero = graph.constant(0.0);
+                return Ok(Node::BinaryOperation(BinaryOperation::Eq, index, zero));
+            }
             Err(syn::Error::new_spanned(
                 expr_unary,
                 "Unsupported unary operator",
             ))
         }
         Expr::Cast(expr_cast) => {
-            let inner_node = build_node(graph, node_map, &expr_cast.expr)?;
+            let inner_node = build_node(graph, helpers, node_map, &expr_cast.expr)?;
 
             if let syn::Type::Path(type_path) = &*expr_cast.ty {
                 let ident = &type_path.path.segments.last().unwrap().ident;
@@ -130,26 +154,21 @@ fn build_node(
             let method_name = method_call.method.to_string();
             if method_call.args.is_empty() {
                 if let Some(builtin) = Builtin::rust_mappings(&method_name) {
-                    let receiver_node = build_node(graph, node_map, &method_call.receiver)?;
+                    let receiver_node =
+                        build_node(graph, helpers, node_map, &method_call.receiver)?;
                     let receiver = graph.insert(receiver_node);
                     return Ok(Node::new_builtin(builtin, receiver));
                 }
             }
 
             if method_call.args.len() == 1 {
-                let binop = match method_name.as_str() {
-                    "powi" => Some(BinaryOperation::PowI),
-                    "powf" => Some(BinaryOperation::PowF),
-                    _ => None,
-                };
-
-                if let Some(binop) = binop {
-                    let left_node = build_node(graph, node_map, &method_call.receiver)?;
+                if let Some(binop) = BinaryOperation::rust_mappings(&method_name) {
+                    let left_node = build_node(graph, helpers, node_map, &method_call.receiver)?;
                     let right_node =
-                        build_node(graph, node_map, method_call.args.first().unwrap())?;
+                        build_node(graph, helpers, node_map, method_call.args.first().unwrap())?;
                     let left = graph.insert(left_node);
                     let right = graph.insert(right_node);
-                    return Ok(Node::new_binary_operation(binop, left, right));
+                    return binary_operation(graph, binop, left, right, method_call);
                 }
             }
 
@@ -159,15 +178,35 @@ fn build_node(
             ))
         }
         Expr::Call(call) => {
+            // Both `sqrt(x)` and the qualified `Float::sqrt(x)` or
+            // `Float::atan2(y, x)` forms name the function last.
             if let Expr::Path(path) = &*call.func {
-                let builtin = Builtin::rust_mappings(&path.path.segments[0].ident.to_string());
-                if let Some(builtin) = builtin {
-                    let node = build_node(graph, node_map, &call.args[0])?;
-                    let arg = graph.insert(node);
-                    // let arg = graph
-                    //     .get_node_index(build_node(graph, node_map, &call.args[0])?)
-                    //     .unwrap();
-                    return Ok(Node::new_builtin(builtin, arg));
+                if let Some(helper) = helpers.iter().find(|h| path.path.is_ident(&h.sig.ident)) {
+                    let id = inline_helper(graph, helpers, node_map, helper, call)?;
+                    return Ok(graph.get_node(id));
+                }
+                let name = path.path.segments.last().unwrap().ident.to_string();
+                if let Some(id) = polynomial_basis(graph, helpers, node_map, &name, call)? {
+                    return Ok(graph.get_node(id));
+                }
+                match call.args.len() {
+                    1 => {
+                        if let Some(builtin) = Builtin::rust_mappings(&name) {
+                            let node = build_node(graph, helpers, node_map, &call.args[0])?;
+                            let arg = graph.insert(node);
+                            return Ok(Node::new_builtin(builtin, arg));
+                        }
+                    }
+                    2 => {
+                        if let Some(binop) = BinaryOperation::rust_mappings(&name) {
+                            let left_node = build_node(graph, helpers, node_map, &call.args[0])?;
+                            let left = graph.insert(left_node);
+                            let right_node = build_node(graph, helpers, node_map, &call.args[1])?;
+                            let right = graph.insert(right_node);
+                            return binary_operation(graph, binop, left, right, call);
+                        }
+                    }
+                    _ => {}
                 }
             };
             Err(syn::Error::new_spanned(call, "Unsupported function call"))
@@ -180,68 +219,471 @@ fn build_node(
                     "`return` without value is unsupported",
                 ));
             };
-            Ok(build_node(graph, node_map, ret_expr)?)
+            Ok(build_node(graph, helpers, node_map, ret_expr)?)
+        }
+        Expr::If(expr_if) => {
+            let Some((_, else_branch)) = &expr_if.else_branch else {
+                return Err(syn::Error::new_spanned(
+                    expr_if,
+                    "`if` without `else` is unsupported",
+                ));
+            };
+            let condition_node = build_node(graph, helpers, node_map, &expr_if.cond)?;
+            let condition = graph.insert(condition_node);
+            let then = build_block(graph, helpers, node_map, &expr_if.then_branch)?;
+            let otherwise_node = build_node(graph, helpers, node_map, else_branch)?;
+            let otherwise = graph.insert(otherwise_node);
+            let select = graph.select(condition, then, otherwise);
+            Ok(graph.get_node(select))
+        }
+        Expr::Block(expr_block) => {
+            let id = build_block(graph, helpers, node_map, &expr_block.block)?;
+            Ok(graph.get_node(id))
+        }
+        Expr::Match(expr_match) => {
+            let scrutinee_node = build_node(graph, helpers, node_map, &expr_match.expr)?;
+            let scrutinee = graph.insert(scrutinee_node);
+            // The arms are folded from the last one, which has to match any
+            // value, into nested selects.
+            let mut result = None;
+            for arm in expr_match.arms.iter().rev() {
+                let mut arm_map = node_map.clone();
+                let mut condition =
+                    pattern_condition(graph, helpers, &mut arm_map, &arm.pat, scrutinee)?;
+                if let Some((_, guard)) = &arm.guard {
+                    let guard_node = build_node(graph, helpers, &arm_map, guard)?;
+                    let guard = graph.insert(guard_node);
+                    condition = Some(match condition {
+                        Some(condition) => graph.insert(Node::new_binary_operation(
+                            BinaryOperation::And,
+                            condition,
+                            guard,
+                        )),
+                        None => guard,
+                    });
+                }
+                let body_node = build_node(graph, helpers, &arm_map, &arm.body)?;
+                let body = graph.insert(body_node);
+                result = Some(match (result, condition) {
+                    (Some(otherwise), Some(condition)) => graph.select(condition, body, otherwise),
+                    (_, None) => body,
+                    (None, Some(_)) => {
+                        return Err(syn::Error::new_spanned(
+                            &arm.pat,
+                            "the last arm of a `match` must match any value",
+                        ));
+                    }
+                });
+            }
+            let Some(result) = result else {
+                return Err(syn::Error::new_spanned(expr_match, "`match` without arms"));
+            };
+            Ok(graph.get_node(result))
         }
         _ => Err(syn::Error::new_spanned(expr, "Unsupported expression")),
     }
 }
 
-pub fn build_graph(pdf_struct: &ItemStruct, value_function: &ItemFn) -> Result<ExpressionGraph> {
-    let types = verify_types(pdf_struct, value_function).unwrap();
-
-    let mut expression_graph = ExpressionGraph::new();
-
-    let mut node_map: HashMap<Ident, NodeId> = HashMap::new();
-    for arg in types.iter() {
-        if let Type::Path(TypePath { path, .. }) = arg.1 {
-            let type_ident = path.segments.last().unwrap().ident.clone();
+/// The node of a call to a two-argument function. The incomplete gamma
+/// functions are only differentiated in `x`, so their shape `a` may not depend
+/// on parameters.
+fn binary_operation(
+    graph: &ExpressionGraph,
+    binop: BinaryOperation,
+    left: NodeId,
+    right: NodeId,
+    call: &impl ToTokens,
+) -> Result<Node> {
+    if matches!(binop, BinaryOperation::GammaP | BinaryOperation::GammaQ)
+        && graph.depends_on_parameters(left)
+    {
+        return Err(Error::new_spanned(
+            call,
+            "the first argument of `gamma_p` and `gamma_q` must not depend on parameters",
+        ));
+    }
+    Ok(Node::new_binary_operation(binop, left, right))
+}
 
-            let variable = Node::new_variable(
-                arg.0.to_string(),
-                match type_ident.to_string().as_str() {
-                    "Parameter" => false,
-                    "Data" => true,
-                    _ => {
-                        return Err(Error::new(
-                            pdf_struct.span(),
-                            "PDF field types must be Parameter or Data",
-                        ));
-                    }
-                },
-            );
-            node_map.insert(arg.0.clone(), expression_graph.insert(variable));
-        }
+/// Expands a call to one of the polynomial bases of
+/// `intermediate_representation::special`, `chebyshev(order, x)` or
+/// `bernstein(index, degree, x)`, into a polynomial in `x`, so that it can be
+/// integrated. Returns `None` for other functions.
+fn polynomial_basis(
+    graph: &mut ExpressionGraph,
+    helpers: &[&ItemFn],
+    node_map: &HashMap<Ident, NodeId>,
+    name: &str,
+    call: &syn::ExprCall,
+) -> Result<Option<NodeId>> {
+    let orders = match name {
+        "chebyshev" => 1,
+        "bernstein" => 2,
+        _ => return Ok(None),
+    };
+    if call.args.len() != orders + 1 {
+        return Err(Error::new_spanned(
+            call,
+            format!("`{}` takes {} arguments", name, orders + 1),
+        ));
+    }
+    let mut integers = Vec::new();
+    for argument in call.args.iter().take(orders) {
+        let Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(integer),
+            ..
+        }) = argument
+        else {
+            return Err(Error::new_spanned(
+                argument,
+                format!("the orders of `{}` must be integer literals", name),
+            ));
+        };
+        integers.push(integer.base10_parse::<u32>()?);
     }
+    let node = build_node(graph, helpers, node_map, &call.args[orders])?;
+    let x = graph.insert(node);
+    let one = graph.constant(1.0);
+    let polynomial = match integers[..] {
+        [order] => {
+            // T_k+1 = 2 x T_k - T_k-1 from T_0 = 1 and T_1 = x.
+            let two = graph.constant(2.0);
+            let doubled = graph.mul(two, x);
+            let (mut previous, mut current) = (one, x);
+            for _ in 0..order {
+                let product = graph.mul(doubled, current);
+                (previous, current) = (current, graph.sub(product, previous));
+            }
+            previous
+        }
+        [index, degree] => {
+            if index > degree {
+                return Err(Error::new_spanned(
+                    call,
+                    "the index of `bernstein` must not exceed its degree",
+                ));
+            }
+            let binomial: Float = (1..=index)
+                .map(|k| (degree - index + k) as Float / k as Float)
+                .product();
+            let binomial = graph.constant(binomial);
+            let complement = graph.sub(one, x);
+            let rising = graph.powi(x, index as i32);
+            let falling = graph.powi(complement, (degree - index) as i32);
+            let product = graph.mul(rising, falling);
+            graph.mul(binomial, product)
+        }
+        _ => unreachable!(),
+    };
+    Ok(Some(polynomial))
+}
 
-    for statement in &value_function.block.stmts {
+/// Inserts the value of a block, its last expression. `let` bindings are
+/// local to the block and may shadow earlier ones.
+fn build_block(
+    graph: &mut ExpressionGraph,
+    helpers: &[&ItemFn],
+    node_map: &HashMap<Ident, NodeId>,
+    block: &syn::Block,
+) -> Result<NodeId> {
+    let mut node_map = node_map.clone();
+    let mut output = None;
+    for statement in &block.stmts {
         match statement {
             Stmt::Local(local) => {
-                if let Pat::Ident(pattern_ident) = &local.pat {
-                    if let Some(init) = &local.init {
-                        let expr = build_node(&mut expression_graph, &node_map, &init.expr)?;
-                        let id = { expression_graph.insert(expr) };
-                        node_map.insert(pattern_ident.ident.clone(), id);
-                    }
-                }
+                let pattern = match &local.pat {
+                    Pat::Type(pattern_type) => &*pattern_type.pat,
+                    pattern => pattern,
+                };
+                let Pat::Ident(pattern_ident) = pattern else {
+                    return Err(Error::new_spanned(pattern, "Unsupported `let` pattern"));
+                };
+                let Some(init) = &local.init else {
+                    return Err(Error::new_spanned(
+                        local,
+                        "`let` without a value is unsupported",
+                    ));
+                };
+                let expr = build_node(graph, helpers, &node_map, &init.expr)?;
+                let id = graph.insert(expr);
+                node_map.insert(pattern_ident.ident.clone(), id);
             }
             Stmt::Expr(expr, ..) => {
-                let expr = build_node(&mut expression_graph, &node_map, expr)?;
-                expression_graph.insert(expr);
+                let expr = build_node(graph, helpers, &node_map, expr)?;
+                output = Some(graph.insert(expr));
             }
             _ => {
                 return Err(Error::new_spanned(statement, "Unsupported statement"));
             }
         }
     }
+    output.ok_or_else(|| Error::new_spanned(block, "block has no result expression"))
+}
 
-    println!("{:?}", expression_graph);
-    Ok(expression_graph)
+/// Inserts the body of a helper function with its arguments bound to the
+/// values passed by `call`. The helper cannot call itself, directly or not,
+/// as it is not available while its body is inlined.
+fn inline_helper(
+    graph: &mut ExpressionGraph,
+    helpers: &[&ItemFn],
+    node_map: &HashMap<Ident, NodeId>,
+    helper: &ItemFn,
+    call: &syn::ExprCall,
+) -> Result<NodeId> {
+    let parameters = function_arguments(helper)?;
+    if parameters.len() != call.args.len() {
+        return Err(Error::new_spanned(
+            call,
+            format!(
+                "`{}` takes {} arguments but {} were given",
+                helper.sig.ident,
+                parameters.len(),
+                call.args.len()
+            ),
+        ));
+    }
+    let mut arguments = HashMap::new();
+    for (parameter, argument) in parameters.into_iter().zip(&call.args) {
+        let node = build_node(graph, helpers, node_map, argument)?;
+        arguments.insert(parameter, graph.insert(node));
+    }
+    let remaining: Vec<&ItemFn> = helpers
+        .iter()
+        .copied()
+        .filter(|h| h.sig.ident != helper.sig.ident)
+        .collect();
+    build_block(graph, &remaining, &arguments, &helper.block)
 }
 
-pub fn verify_types(
+/// Inserts the condition under which `pattern` matches `scrutinee`, or returns
+/// `None` when it matches any value. Bindings are added to `node_map`.
+fn pattern_condition(
+    graph: &mut ExpressionGraph,
+    helpers: &[&ItemFn],
+    node_map: &mut HashMap<Ident, NodeId>,
+    pattern: &Pat,
+    scrutinee: NodeId,
+) -> Result<Option<NodeId>> {
+    match pattern {
+        Pat::Wild(_) => Ok(None),
+        Pat::Ident(pattern_ident) => {
+            node_map.insert(pattern_ident.ident.clone(), scrutinee);
+            match &pattern_ident.subpat {
+                Some((_, subpattern)) => {
+                    pattern_condition(graph, helpers, node_map, subpattern, scrutinee)
+                }
+                None => Ok(None),
+            }
+        }
+        Pat::Lit(literal) => {
+            let node = build_node(graph, helpers, node_map, &Expr::Lit(literal.clone()))?;
+            let value = graph.insert(node);
+            Ok(Some(graph.insert(Node::new_binary_operation(
+                BinaryOperation::Eq,
+                scrutinee,
+                value,
+            ))))
+        }
+        Pat::Range(range) => {
+            let mut bounds = Vec::new();
+            if let Some(start) = &range.start {
+                let node = build_node(graph, helpers, node_map, start)?;
+                bounds.push((BinaryOperation::Ge, graph.insert(node)));
+            }
+            if let Some(end) = &range.end {
+                let binop = match range.limits {
+                    syn::RangeLimits::HalfOpen(_) => BinaryOperation::Lt,
+                    syn::RangeLimits::Closed(_) => BinaryOperation::Le,
+                };
+                let node = build_node(graph, helpers, node_map, end)?;
+                bounds.push((binop, graph.insert(node)));
+            }
+            let tests: Vec<NodeId> = bounds
+                .into_iter()
+                .map(|(binop, bound)| {
+                    graph.insert(Node::new_binary_operation(binop, scrutinee, bound))
+                })
+                .collect();
+            Ok(tests.into_iter().reduce(|condition, test| {
+                graph.insert(Node::new_binary_operation(
+                    BinaryOperation::And,
+                    condition,
+                    test,
+                ))
+            }))
+        }
+        Pat::Or(pattern_or) => {
+            let mut cases = Vec::new();
+            for case in &pattern_or.cases {
+                match pattern_condition(graph, helpers, node_map, case, scrutinee)? {
+                    Some(case) => cases.push(case),
+                    None => return Ok(None),
+                }
+            }
+            Ok(cases.into_iter().reduce(|condition, case| {
+                graph.insert(Node::new_binary_operation(
+                    BinaryOperation::Or,
+                    condition,
+                    case,
+                ))
+            }))
+        }
+        Pat::Paren(pattern_paren) => {
+            pattern_condition(graph, helpers, node_map, &pattern_paren.pat, scrutinee)
+        }
+        _ => Err(Error::new_spanned(pattern, "Unsupported pattern")),
+    }
+}
+
+/// Parses `value_function` into a graph whose first nodes are the struct
+/// fields, in declaration order, and returns it with the id of the node
+/// holding the function's result.
+pub fn build_graph(
     pdf_struct: &ItemStruct,
     value_function: &ItemFn,
-) -> Result<HashMap<Ident, Type>> {
+    helpers: &[ItemFn],
+) -> Result<(ExpressionGraph, NodeId)> {
+    let types = verify_types(pdf_struct, value_function)?;
+    let mut expression_graph = ExpressionGraph::new();
+    insert_fields(&mut expression_graph, &types);
+    let output = parse_body(&mut expression_graph, &types, value_function, helpers)?;
+    Ok((expression_graph, output))
+}
+
+/// Parses `norm` into a graph of its own, laid out like the one of
+/// [`build_graph`] so that the generated function takes the same arrays.
+pub fn build_norm_graph(
+    pdf_struct: &ItemStruct,
+    norm_function: &ItemFn,
+    helpers: &[ItemFn],
+) -> Result<(ExpressionGraph, NodeId)> {
+    let mut expression_graph = ExpressionGraph::new();
+    insert_fields(&mut expression_graph, &struct_fields(pdf_struct)?);
+    let output = extend_graph(&mut expression_graph, pdf_struct, norm_function, helpers)?;
+    Ok((expression_graph, output))
+}
+
+/// Parses a function taking any of the struct fields as arguments, such as
+/// `norm`, into a graph built by [`build_graph`] and returns the id of its
+/// result.
+pub fn extend_graph(
+    graph: &mut ExpressionGraph,
+    pdf_struct: &ItemStruct,
+    function: &ItemFn,
+    helpers: &[ItemFn],
+) -> Result<NodeId> {
+    let types = struct_fields(pdf_struct)?;
+    let arguments = function_arguments(function)?;
+    for arg in &arguments {
+        if !types.iter().any(|(ident, _)| ident == arg) {
+            return Err(Error::new(
+                arg.span(),
+                format!("Function argument `{}` not found in struct fields", arg),
+            ));
+        }
+    }
+    parse_body(graph, &types, function, helpers)
+}
+
+/// Inserts one variable per struct field, `Data` fields being fixed, and
+/// returns their ids. Fields already in the graph keep their ids.
+fn insert_fields(graph: &mut ExpressionGraph, types: &[(Ident, Type)]) -> Vec<NodeId> {
+    types
+        .iter()
+        .map(|(ident, ty)| {
+            let fixed = match ty {
+                Type::Path(TypePath { path, .. }) => path.segments.last().unwrap().ident == "Data",
+                _ => false,
+            };
+            graph.insert(Node::new_variable(ident.to_string(), fixed))
+        })
+        .collect()
+}
+
+/// Parses the body of `function`, whose arguments are struct fields, with
+/// calls to `helpers` inlined.
+fn parse_body(
+    graph: &mut ExpressionGraph,
+    types: &[(Ident, Type)],
+    function: &ItemFn,
+    helpers: &[ItemFn],
+) -> Result<NodeId> {
+    let ids = insert_fields(graph, types);
+    let node_map: HashMap<Ident, NodeId> = types
+        .iter()
+        .map(|(ident, _)| ident.clone())
+        .zip(ids)
+        .collect();
+    let helpers: Vec<&ItemFn> = helpers.iter().collect();
+    build_block(graph, &helpers, &node_map, &function.block)
+}
+
+/// The functions of `intermediate_representation::shapes`, to be inlined like
+/// helpers.
+pub fn library_shapes() -> Result<Vec<ItemFn>> {
+    let file = syn::parse_file(shapes::SOURCE)?;
+    Ok(file
+        .items
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Fn(function) => Some(function),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Marks the `Parameter` field holding the expected number of events of an
+/// extended model. It is part of the parameter layout but not an argument of
+/// the model's functions.
+pub const YIELD_ATTRIBUTE: &str = "yield_parameter";
+
+fn is_yield(field: &Field) -> bool {
+    field
+        .attrs
+        .iter()
+        .any(|attribute| attribute.path().is_ident(YIELD_ATTRIBUTE))
+}
+
+/// Returns the field marked `#[yield_parameter]`, if any.
+pub fn yield_field(pdf_struct: &ItemStruct) -> Result<Option<Ident>> {
+    let mut found: Option<Ident> = None;
+    for field in pdf_struct.fields.iter().filter(|f| is_yield(f)) {
+        if found.is_some() {
+            return Err(Error::new(
+                field.span(),
+                "PDF struct can have at most one `#[yield_parameter]` field",
+            ));
+        }
+        let is_parameter = match &field.ty {
+            Type::Path(TypePath { path, .. }) => path
+                .segments
+                .last()
+                .is_some_and(|last| last.ident == "Parameter"),
+            _ => false,
+        };
+        if !is_parameter {
+            return Err(Error::new(
+                field.ty.span(),
+                "`#[yield_parameter]` field must be of type `Parameter`",
+            ));
+        }
+        found = field.ident.clone();
+    }
+    Ok(found)
+}
+
+/// Removes the `#[yield_parameter]` markers so the struct can be emitted.
+pub fn strip_yield_attribute(pdf_struct: &mut ItemStruct) {
+    for field in pdf_struct.fields.iter_mut() {
+        field
+            .attrs
+            .retain(|attribute| !attribute.path().is_ident(YIELD_ATTRIBUTE));
+    }
+}
+
+/// Returns the struct fields in declaration order, which fixes the layout of
+/// the generated `parameters` and `data` arrays.
+fn struct_fields(pdf_struct: &ItemStruct) -> Result<Vec<(Ident, Type)>> {
     let fields = match &pdf_struct.fields {
         Fields::Named(named) => &named.named,
         _ => {
@@ -252,7 +694,7 @@ pub fn verify_types(
         }
     };
 
-    let mut types = HashMap::new();
+    let mut types = Vec::new();
     for field in fields {
         if let Some(ident) = &field.ident {
             if let Type::Path(TypePath { path, .. }) = &field.ty {
@@ -277,18 +719,21 @@ pub fn verify_types(
                 ));
             }
 
-            types.insert(ident.clone(), field.ty.clone());
+            types.push((ident.clone(), field.ty.clone()));
         } else {
             return Err(Error::new(field.span(), "PDF struct fields must be named"));
         }
     }
+    Ok(types)
+}
 
-    let mut value_args = Vec::new();
-    for input in &value_function.sig.inputs {
+fn function_arguments(function: &ItemFn) -> Result<Vec<Ident>> {
+    let mut arguments = Vec::new();
+    for input in &function.sig.inputs {
         match input {
             FnArg::Typed(pattern_type) => match &*pattern_type.pat {
                 Pat::Ident(PatIdent { ident, .. }) => {
-                    value_args.push(ident.clone());
+                    arguments.push(ident.clone());
                 }
                 other => {
                     return Err(Error::new(other.span(), "Invalid argument"));
@@ -302,8 +747,25 @@ pub fn verify_types(
             }
         }
     }
+    Ok(arguments)
+}
+
+/// Checks the struct fields against the function arguments and returns the
+/// fields in declaration order, which fixes the layout of the generated
+/// `parameters` and `data` arrays.
+pub fn verify_types(
+    pdf_struct: &ItemStruct,
+    value_function: &ItemFn,
+) -> Result<Vec<(Ident, Type)>> {
+    let types = struct_fields(pdf_struct)?;
+    let value_args = function_arguments(value_function)?;
 
-    let struct_field_names: Vec<_> = types.keys().cloned().collect();
+    let struct_field_names: Vec<Ident> = pdf_struct
+        .fields
+        .iter()
+        .filter(|field| !is_yield(field))
+        .filter_map(|field| field.ident.clone())
+        .collect();
     if value_args.len() != struct_field_names.len() {
         return Err(Error::new(
             value_function.sig.ident.span(),
@@ -318,7 +780,7 @@ pub fn verify_types(
     }
 
     for arg in &value_args {
-        if !types.contains_key(arg) {
+        if !struct_field_names.contains(arg) {
             return Err(Error::new(
                 arg.span(),
                 format!("Function argument `{}` not found in struct fields", arg),
@@ -327,3 +789,99 @@ pub fn verify_types(
     }
     Ok(types)
 }
+
+#[cfg(test)]
+mod tests {
+    use intermediate_representation::shapes::*;
+    use syn::parse_quote;
+
+    use super::*;
+
+    /// Every public shape of the library parses, and its graph evaluates to
+    /// the shape itself.
+    #[test]
+    fn library_shapes_build() {
+        let references: Vec<(&str, Vec<Float>, Float)> = vec![
+            ("gaussian", vec![0.5, 0.3, 1.2], gaussian(0.5, 0.3, 1.2)),
+            (
+                "crystal_ball",
+                vec![-3.0, 0.1, 0.8, 1.3, 3.5],
+                crystal_ball(-3.0, 0.1, 0.8, 1.3, 3.5),
+            ),
+            (
+                "crystal_ball",
+                vec![0.4, 0.1, 0.8, 1.3, 3.5],
+                crystal_ball(0.4, 0.1, 0.8, 1.3, 3.5),
+            ),
+            (
+                "double_sided_crystal_ball",
+                vec![-3.0, 0.0, 1.0, 1.2, 2.5, 1.8, 4.0],
+                double_sided_crystal_ball(-3.0, 0.0, 1.0, 1.2, 2.5, 1.8, 4.0),
+            ),
+            (
+                "double_sided_crystal_ball",
+                vec![4.0, 0.0, 1.0, 1.2, 2.5, 1.8, 4.0],
+                double_sided_crystal_ball(4.0, 0.0, 1.0, 1.2, 2.5, 1.8, 4.0),
+            ),
+            (
+                "breit_wigner",
+                vec![1.2, 1.0, 0.6],
+                breit_wigner(1.2, 1.0, 0.6),
+            ),
+            (
+                "voigtian",
+                vec![1.5, 0.2, 0.5, 0.4],
+                voigtian(1.5, 0.2, 0.5, 0.4),
+            ),
+            (
+                "argus",
+                vec![5.24, 5.29, -8.0, 0.5],
+                argus(5.24, 5.29, -8.0, 0.5),
+            ),
+            (
+                "johnson_su",
+                vec![2.0, 0.5, 1.5, -0.4, 1.7],
+                johnson_su(2.0, 0.5, 1.5, -0.4, 1.7),
+            ),
+        ];
+
+        let library = library_shapes().unwrap();
+        for function in &library {
+            let name = function.sig.ident.to_string();
+            if matches!(function.vis, syn::Visibility::Public(_)) {
+                assert!(
+                    references.iter().any(|(shape, _, _)| *shape == name),
+                    "no reference for `{}`",
+                    name
+                );
+            }
+        }
+        for (name, arguments, expected) in references {
+            let function = library
+                .iter()
+                .find(|function| function.sig.ident == name)
+                .unwrap();
+            let types: Vec<(Ident, Type)> = function_arguments(function)
+                .unwrap()
+                .into_iter()
+                .map(|ident| (ident, parse_quote!(Float)))
+                .collect();
+            let mut graph = ExpressionGraph::new();
+            let output = parse_body(&mut graph, &types, function, &library)
+                .unwrap_or_else(|e| panic!("`{}`: {}", name, e));
+            let values = types
+                .iter()
+                .map(|(ident, _)| ident.to_string())
+                .zip(arguments)
+                .collect();
+            let value = graph.evaluate(output, &values);
+            assert!(
+                (value - expected).abs() < 1e-12 * expected.abs(),
+                "`{}`: {} != {}",
+                name,
+                value,
+                expected
+            );
+        }
+    }
+}
@@ -11,6 +11,9 @@ pub struct PdfInput {
     pub distribution: ItemFn,
     pub likelihood: Option<ItemFn>,
     pub norm: Option<ItemFn>,
+    /// Any other functions, which are inlined where the model functions call
+    /// them.
+    pub helpers: Vec<ItemFn>,
 }
 
 impl Parse for PdfInput {
@@ -19,6 +22,7 @@ impl Parse for PdfInput {
         let mut distribution = None;
         let mut likelihood = None;
         let mut norm = None;
+        let mut helpers: Vec<ItemFn> = Vec::new();
         while !input.is_empty() {
             let item: Item = input.parse()?;
 
@@ -64,10 +68,13 @@ impl Parse for PdfInput {
                             norm = Some(f);
                         }
                         _ => {
-                            return Err(syn::Error::new(
-                                f.sig.ident.span(),
-                                "PDF definition can only contain 'distribution' 'likelihood', and 'norm' functions",
-                            ));
+                            if helpers.iter().any(|h| h.sig.ident == f.sig.ident) {
+                                return Err(syn::Error::new(
+                                    f.sig.ident.span(),
+                                    format!("duplicate function definition for '{}'", fn_name),
+                                ));
+                            }
+                            helpers.push(f);
                         }
                     }
                 }
@@ -75,7 +82,7 @@ impl Parse for PdfInput {
                 _ => {
                     return Err(syn::Error::new(
                         item.span(),
-                        "Unexpected item. Only a single struct and functions named 'value', 'norm', or 'grad' are allowed.",
+                        "Unexpected item. Only a single struct and functions are allowed.",
                     ));
                 }
             }
@@ -97,6 +104,7 @@ impl Parse for PdfInput {
             distribution,
             likelihood,
             norm,
+            helpers,
         })
     }
     //     while !input.is_empty() {
@@ -4,50 +4,125 @@ use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
 
 use intermediate_representation::{
+    branch::Guard,
     constant::Constant,
     expression::{ExpressionGraph, Node, NodeId},
 };
 
+fn val_name(id: NodeId) -> Ident {
+    format_ident!("v{}", id)
+}
+
+fn adj_name(id: NodeId) -> Ident {
+    format_ident!("a{}", id)
+}
+
+/// Assigns positions in the `parameters` and `data` arrays to every variable
+/// of the graph. `parse::build_graph` inserts the struct fields first and in
+/// declaration order, so the layout follows the struct even for variables the
+/// output does not depend on.
+fn variable_layout(graph: &ExpressionGraph) -> (HashMap<NodeId, usize>, HashMap<NodeId, usize>) {
+    let mut parameter_map: HashMap<NodeId, usize> = HashMap::new();
+    let mut data_map: HashMap<NodeId, usize> = HashMap::new();
+    for id in 0..graph.len() {
+        if let Node::Variable(variable) = graph.get_node(id) {
+            if variable.fixed {
+                data_map.insert(id, data_map.len());
+            } else {
+                parameter_map.insert(id, parameter_map.len());
+            }
+        }
+    }
+    (parameter_map, data_map)
+}
+
+fn forward_code(
+    graph: &ExpressionGraph,
+    id: NodeId,
+    parameter_map: &HashMap<NodeId, usize>,
+    data_map: &HashMap<NodeId, usize>,
+) -> TokenStream {
+    let result_name = val_name(id);
+    match graph.get_node(id) {
+        Node::Constant(number) => match number {
+            Constant::Float(value) => quote! { let #result_name = #value; },
+            Constant::Integer(value) => quote! { let #result_name = #value; },
+        },
+        Node::Variable(_) => {
+            if let Some(data_index) = data_map.get(&id) {
+                quote! { let #result_name = data[#data_index]; }
+            } else {
+                let parameter_index = parameter_map[&id];
+                quote! {let #result_name = parameters[#parameter_index]; }
+            }
+        }
+        Node::Builtin(builtin, argument_id) => {
+            builtin.generate_forward(result_name, val_name(argument_id))
+        }
+        Node::BinaryOperation(binop, left_id, right_id) => {
+            binop.generate_forward(result_name, val_name(left_id), val_name(right_id))
+        }
+        Node::Select(condition_id, then_id, otherwise_id) => {
+            let (condition, then, otherwise) = (
+                val_name(condition_id),
+                val_name(then_id),
+                val_name(otherwise_id),
+            );
+            quote! { let #result_name = if #condition != 0.0 { #then } else { #otherwise }; }
+        }
+    }
+}
+
+/// The test that all branches of `guard` are taken.
+fn guard_test(graph: &ExpressionGraph, guard: &Guard) -> TokenStream {
+    let tests = guard.iter().map(|&(select, taken)| {
+        let Node::Select(condition_id, _, _) = graph.get_node(select) else {
+            unreachable!("guards only hold selects");
+        };
+        let condition = val_name(condition_id);
+        if taken {
+            quote! { #condition != 0.0 }
+        } else {
+            quote! { #condition == 0.0 }
+        }
+    });
+    quote! { #(#tests)&&* }
+}
+
+/// Forward code of node `id` that only evaluates it when the branches it is
+/// needed under are taken, leaving zero otherwise.
+fn guarded_forward_code(
+    graph: &ExpressionGraph,
+    id: NodeId,
+    guards: &HashMap<NodeId, Guard>,
+    parameter_map: &HashMap<NodeId, usize>,
+    data_map: &HashMap<NodeId, usize>,
+) -> TokenStream {
+    let code = forward_code(graph, id, parameter_map, data_map);
+    let guard = &guards[&id];
+    if guard.is_empty() || matches!(graph.get_node(id), Node::Constant(_) | Node::Variable(_)) {
+        return code;
+    }
+    let result_name = val_name(id);
+    let test = guard_test(graph, guard);
+    quote! {
+        let #result_name = if #test {
+            #code
+            #result_name
+        } else {
+            0.0
+        };
+    }
+}
+
 pub fn translate(graph: &ExpressionGraph, output_id: NodeId, signature: Ident) -> TokenStream {
-    let mut parameter_map: HashMap<usize, usize> = HashMap::new();
-    let mut data_map: HashMap<usize, usize> = HashMap::new();
+    let (parameter_map, data_map) = variable_layout(graph);
     let sorted_nodes = graph.topological_sort(output_id);
-
-    let val_name = |id: NodeId| format_ident!("v{}", id);
-    let adj_name = |id: NodeId| format_ident!("a{}", id);
+    let guards = graph.guards(&[output_id]);
 
     let forward_pass_code: Vec<TokenStream> = sorted_nodes
         .iter()
-        .map(|&id| {
-            let node = graph.get_node(id);
-            let result_name = val_name(id);
-            match node {
-                Node::Constant(number) => match number {
-                    Constant::Float(value) => quote! { let #result_name = #value; },
-                    Constant::Integer(value) => quote! { let #result_name = #value; },
-                },
-                Node::Variable(variable) => {
-                    // let var_name = format_ident!("{}", variable.name);
-                    // input_variable_ids.push(id);
-                    if variable.fixed {
-                        let data_index = data_map.len();
-                        data_map.insert(id, data_index);
-                        quote! { let #result_name = data[#data_index]; }
-                    } else {
-                        let parameter_index = parameter_map.len();
-                        parameter_map.insert(id, parameter_index);
-                        quote! {let #result_name = parameters[#parameter_index]; }
-                    }
-                    // quote! { let #result_name = #var_name; }
-                }
-                Node::Builtin(builtin, argument_id) => {
-                    builtin.generate_forward(result_name, val_name(argument_id))
-                }
-                Node::BinaryOperation(binop, left_id, right_id) => {
-                    binop.generate_forward(result_name, val_name(left_id), val_name(right_id))
-                }
-            }
-        })
+        .map(|&id| guarded_forward_code(graph, id, &guards, &parameter_map, &data_map))
         .collect();
 
     let mut reverse_pass_code: Vec<TokenStream> = sorted_nodes
@@ -76,9 +151,33 @@ pub fn translate(graph: &ExpressionGraph, output_id: NodeId, signature: Ident) -
                 adj_name(left_id),
                 adj_name(right_id),
             ),
+            Node::Select(condition_id, then_id, otherwise_id) => {
+                let (condition, then_adj, otherwise_adj) = (
+                    val_name(condition_id),
+                    adj_name(then_id),
+                    adj_name(otherwise_id),
+                );
+                quote! {
+                    if #condition != 0.0 {
+                        #then_adj += #propagate;
+                    } else {
+                        #otherwise_adj += #propagate;
+                    }
+                }
+            }
             Node::Constant(_) | Node::Variable(_) => quote! {},
         };
-        reverse_pass_code.push(reverse);
+        let guard = &guards[&id];
+        if guard.is_empty() || reverse.is_empty() {
+            reverse_pass_code.push(reverse);
+        } else {
+            let test = guard_test(graph, guard);
+            reverse_pass_code.push(quote! {
+                if #test {
+                    #reverse
+                }
+            });
+        }
     }
 
     let final_value_name = val_name(output_id);
@@ -94,17 +193,21 @@ pub fn translate(graph: &ExpressionGraph, output_id: NodeId, signature: Ident) -
     };
 
     let function_signature = quote! {
+        #[allow(clippy::approx_constant)]
         pub fn #signature(#parameters, #data) -> (Float, [Float; #parameter_cols])
     };
 
     let mut input_adj_names = Vec::new();
-    input_adj_names.resize(parameter_cols, adj_name(0));
+    input_adj_names.resize(parameter_cols, quote! { 0.0 });
 
     parameter_map.iter().for_each(|(&key, &val)| {
-        input_adj_names[val] = adj_name(key);
+        if sorted_nodes.contains(&key) {
+            let adj = adj_name(key);
+            input_adj_names[val] = quote! { #adj };
+        }
     });
 
-    let b = quote! {
+    quote! {
         #function_signature {
             #(#forward_pass_code)*
             #(#reverse_pass_code)*
@@ -112,7 +215,51 @@ pub fn translate(graph: &ExpressionGraph, output_id: NodeId, signature: Ident) -
             let gradient = [#(#input_adj_names),*];
             (final_value, gradient)
         }
-    };
-    println!("{}", b.to_string());
-    b
+    }
+}
+
+/// Emits a function returning the value, gradient and Hessian of the output
+/// node, with the second derivatives obtained by forward-over-reverse
+/// differentiation of the graph.
+pub fn translate_hessian(
+    graph: &mut ExpressionGraph,
+    output_id: NodeId,
+    signature: Ident,
+) -> TokenStream {
+    let (parameter_map, data_map) = variable_layout(graph);
+    let mut parameters: Vec<NodeId> = parameter_map.keys().copied().collect();
+    parameters.sort_by_key(|id| parameter_map[id]);
+
+    let (gradient, hessian) = graph.hessian(output_id, &parameters);
+
+    let mut outputs = vec![output_id];
+    outputs.extend(&gradient);
+    hessian.iter().for_each(|row| outputs.extend(row));
+    let sorted_nodes = graph.topological_sort_all(&outputs);
+    let guards = graph.guards(&outputs);
+
+    let forward_pass_code: Vec<TokenStream> = sorted_nodes
+        .iter()
+        .map(|&id| guarded_forward_code(graph, id, &guards, &parameter_map, &data_map))
+        .collect();
+
+    let data_cols = data_map.len();
+    let parameter_cols = parameter_map.len();
+    let final_value_name = val_name(output_id);
+    let gradient_names = gradient.iter().map(|&id| val_name(id));
+    let hessian_rows = hessian.iter().map(|row| {
+        let names = row.iter().map(|&id| val_name(id));
+        quote! { [#(#names),*] }
+    });
+
+    quote! {
+        #[allow(clippy::approx_constant, clippy::type_complexity)]
+        pub fn #signature(
+            parameters: [Float; #parameter_cols],
+            data: [Float; #data_cols],
+        ) -> (Float, [Float; #parameter_cols], [[Float; #parameter_cols]; #parameter_cols]) {
+            #(#forward_pass_code)*
+            (#final_value_name, [#(#gradient_names),*], [#(#hessian_rows),*])
+        }
+    }
 }
@@ -0,0 +1,247 @@
+use intermediate_representation::Float;
+
+use crate::histogram::Histogram;
+use crate::integration::gauss_legendre;
+use crate::matrix::Matrix;
+use crate::minimize::Objective;
+use crate::model::Model;
+use crate::parameter::Parameter;
+
+/// How the model is turned into an expected bin content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinEvaluation {
+    /// Density at the bin centre times the bin width.
+    Centre,
+    /// Density integrated over the bin by Gauss-Legendre quadrature.
+    Integrated,
+}
+
+/// Expected contents of each bin and their gradients. With an expected number
+/// of events the density is normalized over the model's own range, so that
+/// events falling outside the histogram are accounted for. Otherwise it is
+/// normalized over the histogram and scaled by its number of entries.
+fn expected_counts<M: Model + ?Sized>(
+    model: &M,
+    histogram: &Histogram,
+    evaluation: BinEvaluation,
+    parameters: &[Float],
+) -> Vec<(Float, Vec<Float>)> {
+    let n = parameters.len();
+    let mut density =
+        |x: Float, gradient: &mut [Float]| model.distribution(parameters, &[x], gradient);
+    let integrals: Vec<(Float, Vec<Float>)> = (0..histogram.bins())
+        .map(|bin| match evaluation {
+            BinEvaluation::Centre => {
+                let width = histogram.width(bin);
+                let mut gradient = vec![0.0; n];
+                let value = density(histogram.center(bin), &mut gradient);
+                gradient.iter_mut().for_each(|g| *g *= width);
+                (value * width, gradient)
+            }
+            BinEvaluation::Integrated => {
+                let (low, high) = histogram.bin_range(bin);
+                gauss_legendre(low, high, n, &mut density)
+            }
+        })
+        .collect();
+
+    // Normalization and the gradient of its logarithm, then the number of
+    // events and its gradient.
+    let mut normalization_gradient = vec![0.0; n];
+    let mut expected_gradient = vec![0.0; n];
+    let (normalization, expected) = match model.expected_events(parameters, &mut expected_gradient)
+    {
+        Some(expected) => {
+            let normalization = model
+                .log_normalization(parameters, &mut normalization_gradient)
+                .exp();
+            (normalization, expected)
+        }
+        None => {
+            expected_gradient.fill(0.0);
+            let normalization: Float = integrals.iter().map(|(integral, _)| integral).sum();
+            for (_, gradient) in &integrals {
+                normalization_gradient
+                    .iter_mut()
+                    .zip(gradient)
+                    .for_each(|(d, g)| *d += g / normalization);
+            }
+            (normalization, histogram.total())
+        }
+    };
+
+    integrals
+        .into_iter()
+        .map(|(integral, integral_gradient)| {
+            let fraction = integral / normalization;
+            let gradient = integral_gradient
+                .iter()
+                .zip(&normalization_gradient)
+                .zip(&expected_gradient)
+                .map(|((g, d), e)| (g / normalization - fraction * d) * expected + fraction * e)
+                .collect();
+            (fraction * expected, gradient)
+        })
+        .collect()
+}
+
+fn check_histogram<M: Model + ?Sized>(model: &M, histogram: &Histogram) {
+    assert_eq!(
+        model.data_names(),
+        vec![histogram.column().to_string()],
+        "binned fits need a one-dimensional model over the histogram's observable"
+    );
+}
+
+/// Binned Poisson negative log-likelihood, in the saturated form
+/// `sum(mu - n + n ln(n / mu))` which is zero for a perfect fit. For weighted
+/// histograms HESSE reports the sandwich covariance with the sums of squared
+/// weights as the variances of the contents.
+pub struct BinnedNegativeLogLikelihood<'a, M: Model + ?Sized> {
+    model: &'a M,
+    histogram: &'a Histogram,
+    evaluation: BinEvaluation,
+}
+
+impl<'a, M: Model + ?Sized> BinnedNegativeLogLikelihood<'a, M> {
+    pub fn new(model: &'a M, histogram: &'a Histogram) -> Self {
+        check_histogram(model, histogram);
+        Self {
+            model,
+            histogram,
+            evaluation: BinEvaluation::Integrated,
+        }
+    }
+
+    pub fn with_evaluation(mut self, evaluation: BinEvaluation) -> Self {
+        self.evaluation = evaluation;
+        self
+    }
+
+    pub fn parameters(&self) -> Vec<Parameter> {
+        self.model.parameters()
+    }
+}
+
+impl<M: Model + ?Sized> Objective for BinnedNegativeLogLikelihood<'_, M> {
+    fn dimension(&self) -> usize {
+        self.model.parameter_names().len()
+    }
+
+    fn error_definition(&self) -> Float {
+        0.5
+    }
+
+    fn value_and_gradient(&self, parameters: &[Float]) -> (Float, Vec<Float>) {
+        let expected = expected_counts(self.model, self.histogram, self.evaluation, parameters);
+        let mut value = 0.0;
+        let mut gradient = vec![0.0; parameters.len()];
+        for ((mu, mu_gradient), &observed) in expected.iter().zip(self.histogram.counts()) {
+            value += mu - observed;
+            if observed > 0.0 {
+                value += observed * (observed / mu).ln();
+            }
+            let derivative = 1.0 - observed / mu;
+            gradient
+                .iter_mut()
+                .zip(mu_gradient)
+                .for_each(|(g, d)| *g += derivative * d);
+        }
+        (value, gradient)
+    }
+
+    /// Sum over bins of `(w2 / mu^2) g g^T`, where `w2` is the variance of the
+    /// content and `g` the gradient of `mu`.
+    fn gradient_covariance(&self, parameters: &[Float]) -> Option<Matrix> {
+        if !self.histogram.is_weighted() {
+            return None;
+        }
+        let expected = expected_counts(self.model, self.histogram, self.evaluation, parameters);
+        let mut covariance = Matrix::zeros(parameters.len());
+        for ((mu, mu_gradient), &variance) in expected.iter().zip(self.histogram.squared_weights())
+        {
+            covariance.add_outer_product(variance / (mu * mu), mu_gradient, mu_gradient);
+        }
+        Some(covariance)
+    }
+}
+
+/// Which variance enters the denominator of the chi-square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChiSquareKind {
+    /// Variances of the observed contents, the sums of squared weights, with
+    /// empty bins skipped.
+    Neyman,
+    /// Expected counts, which are the variances of unweighted histograms only.
+    Pearson,
+}
+
+pub struct ChiSquare<'a, M: Model + ?Sized> {
+    model: &'a M,
+    histogram: &'a Histogram,
+    kind: ChiSquareKind,
+    evaluation: BinEvaluation,
+}
+
+impl<'a, M: Model + ?Sized> ChiSquare<'a, M> {
+    pub fn new(model: &'a M, histogram: &'a Histogram, kind: ChiSquareKind) -> Self {
+        check_histogram(model, histogram);
+        Self {
+            model,
+            histogram,
+            kind,
+            evaluation: BinEvaluation::Integrated,
+        }
+    }
+
+    pub fn with_evaluation(mut self, evaluation: BinEvaluation) -> Self {
+        self.evaluation = evaluation;
+        self
+    }
+
+    pub fn parameters(&self) -> Vec<Parameter> {
+        self.model.parameters()
+    }
+}
+
+impl<M: Model + ?Sized> Objective for ChiSquare<'_, M> {
+    fn dimension(&self) -> usize {
+        self.model.parameter_names().len()
+    }
+
+    fn error_definition(&self) -> Float {
+        1.0
+    }
+
+    fn value_and_gradient(&self, parameters: &[Float]) -> (Float, Vec<Float>) {
+        let expected = expected_counts(self.model, self.histogram, self.evaluation, parameters);
+        let mut value = 0.0;
+        let mut gradient = vec![0.0; parameters.len()];
+        let bins = self
+            .histogram
+            .counts()
+            .iter()
+            .zip(self.histogram.squared_weights());
+        for ((mu, mu_gradient), (&observed, &variance)) in expected.iter().zip(bins) {
+            let residual = observed - mu;
+            let derivative = match self.kind {
+                ChiSquareKind::Neyman => {
+                    if variance <= 0.0 {
+                        continue;
+                    }
+                    value += residual * residual / variance;
+                    -2.0 * residual / variance
+                }
+                ChiSquareKind::Pearson => {
+                    value += residual * residual / mu;
+                    1.0 - observed * observed / (mu * mu)
+                }
+            };
+            gradient
+                .iter_mut()
+                .zip(mu_gradient)
+                .for_each(|(g, d)| *g += derivative * d);
+        }
+        (value, gradient)
+    }
+}
@@ -0,0 +1,687 @@
+use std::sync::RwLock;
+
+use intermediate_representation::Float;
+
+use crate::data::Data;
+use crate::integration::{TOLERANCE, gauss_kronrod, log_integral};
+use crate::model::Model;
+use crate::parameter::Parameter;
+
+/// A model combined into a composite, with the position in the composite's
+/// parameter vector of each of its parameters.
+struct Component<'a> {
+    model: &'a dyn Model,
+    indices: Vec<usize>,
+}
+
+impl Component<'_> {
+    fn local(&self, parameters: &[Float]) -> Vec<Float> {
+        self.indices.iter().map(|&i| parameters[i]).collect()
+    }
+
+    /// Adds `scale` times the component's `local` gradient into `global`.
+    fn scatter(&self, scale: Float, local: &[Float], global: &mut [Float]) {
+        for (&i, g) in self.indices.iter().zip(local) {
+            global[i] += scale * g;
+        }
+    }
+}
+
+/// Appends `parameter` to `parameters` unless one of the same name is already
+/// present, returning its index.
+fn parameter_index(parameters: &mut Vec<Parameter>, parameter: Parameter) -> usize {
+    match parameters.iter().position(|p| p.name() == parameter.name()) {
+        Some(index) => index,
+        None => {
+            parameters.push(parameter);
+            parameters.len() - 1
+        }
+    }
+}
+
+/// Log-normalization of each component with its local gradient.
+type Normalizations = Vec<(Float, Vec<Float>)>;
+
+/// Log-normalizations of the components at the last parameters they were
+/// evaluated for. A likelihood evaluates every event at the same parameters,
+/// so the integrals are computed once per evaluation rather than once per
+/// event.
+#[derive(Default)]
+struct NormalizationCache {
+    entry: RwLock<Option<(Vec<Float>, Normalizations)>>,
+}
+
+impl NormalizationCache {
+    fn get(&self, parameters: &[Float], components: &[Component]) -> Normalizations {
+        if let Some((key, normalizations)) = &*self.entry.read().unwrap()
+            && key == parameters
+        {
+            return normalizations.clone();
+        }
+        let normalizations: Normalizations = components
+            .iter()
+            .map(|component| {
+                let local = component.local(parameters);
+                let mut gradient = vec![0.0; local.len()];
+                let value = component.model.log_normalization(&local, &mut gradient);
+                (value, gradient)
+            })
+            .collect();
+        *self.entry.write().unwrap() = Some((parameters.to_vec(), normalizations.clone()));
+        normalizations
+    }
+}
+
+/// How the coefficients of a [`SumModel`] weight its components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coefficients {
+    /// Fractions of the total, with an optional remainder component taking
+    /// one minus their sum.
+    Fractions,
+    /// Expected numbers of events, whose sum makes the model extended.
+    Yields,
+}
+
+/// Weighted sum of the normalized distributions of several models over the
+/// same observables, such as a signal and a background. Each component is
+/// divided by its own [`Model::log_normalization`], and the sum is
+/// normalized, so the composite's own log-normalization is zero.
+/// Parameters are identified across components by name, as in
+/// [`SimultaneousLikelihood`](crate::simultaneous::SimultaneousLikelihood),
+/// and listed in order of first appearance.
+pub struct SumModel<'a> {
+    coefficients: Coefficients,
+    components: Vec<Component<'a>>,
+    /// Index of each component's coefficient, `None` for the remainder.
+    coefficient_indices: Vec<Option<usize>>,
+    parameters: Vec<Parameter>,
+    normalizations: NormalizationCache,
+}
+
+impl<'a> SumModel<'a> {
+    pub fn new(coefficients: Coefficients) -> Self {
+        Self {
+            coefficients,
+            components: Vec::new(),
+            coefficient_indices: Vec::new(),
+            parameters: Vec::new(),
+            normalizations: NormalizationCache::default(),
+        }
+    }
+
+    fn push(&mut self, model: &'a dyn Model, coefficient: Option<Parameter>) {
+        assert!(
+            !self.coefficient_indices.contains(&None),
+            "the remainder must be the last component"
+        );
+        if let Some(first) = self.components.first() {
+            assert_eq!(
+                model.data_names(),
+                first.model.data_names(),
+                "components of a sum must share their observables"
+            );
+        }
+        let indices = model
+            .parameters()
+            .into_iter()
+            .map(|parameter| parameter_index(&mut self.parameters, parameter))
+            .collect();
+        let coefficient = coefficient.map(|c| parameter_index(&mut self.parameters, c));
+        self.components.push(Component { model, indices });
+        self.coefficient_indices.push(coefficient);
+    }
+
+    /// Adds a component weighted by the `coefficient` parameter.
+    pub fn with_component(mut self, model: &'a dyn Model, coefficient: Parameter) -> Self {
+        self.push(model, Some(coefficient));
+        self
+    }
+
+    /// Adds a last component weighted by one minus the sum of the fractions.
+    pub fn with_remainder(mut self, model: &'a dyn Model) -> Self {
+        assert_eq!(
+            self.coefficients,
+            Coefficients::Fractions,
+            "only a sum of fractions has a remainder"
+        );
+        self.push(model, None);
+        self
+    }
+
+    /// Indices of the component's parameters, in the model's layout order.
+    pub fn component_parameters(&self, component: usize) -> &[usize] {
+        &self.components[component].indices
+    }
+
+    fn coefficient(&self, parameters: &[Float], index: Option<usize>) -> Float {
+        match index {
+            Some(index) => parameters[index],
+            None => {
+                1.0 - self
+                    .coefficient_indices
+                    .iter()
+                    .flatten()
+                    .map(|&i| parameters[i])
+                    .sum::<Float>()
+            }
+        }
+    }
+
+    fn first(&self) -> &Component<'a> {
+        self.components.first().expect("the sum has no components")
+    }
+}
+
+impl Model for SumModel<'_> {
+    fn parameter_names(&self) -> Vec<String> {
+        self.parameters
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect()
+    }
+
+    fn data_names(&self) -> Vec<String> {
+        self.first().model.data_names()
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        self.parameters.clone()
+    }
+
+    fn data(&self) -> Vec<Data> {
+        self.first().model.data()
+    }
+
+    fn expected_events(&self, parameters: &[Float], gradient: &mut [Float]) -> Option<Float> {
+        if self.coefficients == Coefficients::Fractions {
+            return None;
+        }
+        gradient.fill(0.0);
+        let mut expected = 0.0;
+        for &index in self.coefficient_indices.iter().flatten() {
+            gradient[index] += 1.0;
+            expected += parameters[index];
+        }
+        Some(expected)
+    }
+
+    fn distribution(&self, parameters: &[Float], event: &[Float], gradient: &mut [Float]) -> Float {
+        let value = self.log_likelihood(parameters, event, gradient).exp();
+        gradient.iter_mut().for_each(|g| *g *= value);
+        value
+    }
+
+    /// The logarithm of the sum, accumulated in log space from the
+    /// components' log-likelihoods so that no term underflows.
+    fn log_likelihood(
+        &self,
+        parameters: &[Float],
+        event: &[Float],
+        gradient: &mut [Float],
+    ) -> Float {
+        let normalizations = self.normalizations.get(parameters, &self.components);
+        let mut terms = Vec::with_capacity(self.components.len());
+        let mut term_gradients = Vec::with_capacity(self.components.len());
+        for ((component, &index), (log_norm, norm_gradient)) in self
+            .components
+            .iter()
+            .zip(&self.coefficient_indices)
+            .zip(&normalizations)
+        {
+            let local = component.local(parameters);
+            let mut local_gradient = vec![0.0; local.len()];
+            let value = component
+                .model
+                .log_likelihood(&local, event, &mut local_gradient);
+            let mut term_gradient = vec![0.0; parameters.len()];
+            component.scatter(1.0, &local_gradient, &mut term_gradient);
+            component.scatter(-1.0, norm_gradient, &mut term_gradient);
+            let coefficient = self.coefficient(parameters, index);
+            match index {
+                Some(index) => term_gradient[index] += 1.0 / coefficient,
+                None => {
+                    for &fraction in self.coefficient_indices.iter().flatten() {
+                        term_gradient[fraction] -= 1.0 / coefficient;
+                    }
+                }
+            }
+            terms.push(coefficient.ln() + value - log_norm);
+            term_gradients.push(term_gradient);
+        }
+
+        gradient.fill(0.0);
+        let largest = terms.iter().copied().fold(Float::NEG_INFINITY, Float::max);
+        if largest == Float::NEG_INFINITY {
+            return largest;
+        }
+        let weights: Vec<Float> = terms.iter().map(|t| (t - largest).exp()).collect();
+        let total: Float = weights.iter().sum();
+        for (weight, term_gradient) in weights.iter().zip(&term_gradients) {
+            if *weight > 0.0 {
+                gradient
+                    .iter_mut()
+                    .zip(term_gradient)
+                    .for_each(|(g, t)| *g += weight / total * t);
+            }
+        }
+        let mut value = largest + total.ln();
+        let mut expected_gradient = vec![0.0; parameters.len()];
+        if let Some(expected) = self.expected_events(parameters, &mut expected_gradient) {
+            value -= expected.ln();
+            gradient
+                .iter_mut()
+                .zip(&expected_gradient)
+                .for_each(|(g, e)| *g -= e / expected);
+        }
+        value
+    }
+}
+
+/// A component of a [`ProductModel`], with the position in the product's
+/// event of each of its observables.
+struct Factor<'a> {
+    component: Component<'a>,
+    columns: Vec<usize>,
+}
+
+impl Factor<'_> {
+    fn event(&self, event: &[Float]) -> Vec<Float> {
+        self.columns.iter().map(|&c| event[c]).collect()
+    }
+
+    /// Adds the component's row-major `local` Hessian into `global`.
+    fn scatter_hessian(&self, local: &[Float], global: &mut [Float]) {
+        let indices = &self.component.indices;
+        let (n, size) = (indices.len(), global.len().isqrt());
+        for (a, &i) in indices.iter().enumerate() {
+            for (b, &j) in indices.iter().enumerate() {
+                global[i * size + j] += local[a * n + b];
+            }
+        }
+    }
+}
+
+/// Product of models over independent observables, such as a mass, a decay
+/// time and an angle each described by its own model. The log-likelihood and
+/// log-normalization are the sums of the components'. Parameters are shared
+/// by name as in [`SumModel`]. The product's observables are those of the
+/// components in order, optionally renamed to match the dataset's columns.
+/// At most one component may be extended.
+#[derive(Default)]
+pub struct ProductModel<'a> {
+    factors: Vec<Factor<'a>>,
+    parameters: Vec<Parameter>,
+    data: Vec<Data>,
+    /// Index of the extended component, if any.
+    extended: Option<usize>,
+}
+
+impl<'a> ProductModel<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a component whose observables keep their names.
+    pub fn with_component(self, model: &'a dyn Model) -> Self {
+        let names = model.data_names();
+        let columns: Vec<&str> = names.iter().map(String::as_str).collect();
+        self.with_mapped_component(model, &columns)
+    }
+
+    /// Adds a component whose observables, in its layout order, are the
+    /// product's `columns`.
+    pub fn with_mapped_component(mut self, model: &'a dyn Model, columns: &[&str]) -> Self {
+        let data = model.data();
+        assert_eq!(
+            columns.len(),
+            data.len(),
+            "a column is needed for each of the component's observables"
+        );
+        let values = model.parameter_values();
+        if model
+            .expected_events(&values, &mut vec![0.0; values.len()])
+            .is_some()
+        {
+            assert!(
+                self.extended.is_none(),
+                "at most one component of a product may be extended"
+            );
+            self.extended = Some(self.factors.len());
+        }
+        let columns = columns
+            .iter()
+            .zip(data)
+            .map(|(&column, data)| {
+                assert!(
+                    self.data.iter().all(|d| d.name() != column),
+                    "observable `{}` appears in several components",
+                    column
+                );
+                self.data
+                    .push(Data::new(column, data.distribution().clone()));
+                self.data.len() - 1
+            })
+            .collect();
+        let indices = model
+            .parameters()
+            .into_iter()
+            .map(|parameter| parameter_index(&mut self.parameters, parameter))
+            .collect();
+        self.factors.push(Factor {
+            component: Component { model, indices },
+            columns,
+        });
+        self
+    }
+
+    /// Indices of the component's parameters, in the model's layout order.
+    pub fn component_parameters(&self, component: usize) -> &[usize] {
+        &self.factors[component].component.indices
+    }
+
+    /// Indices of the component's observables in the product's events.
+    pub fn component_columns(&self, component: usize) -> &[usize] {
+        &self.factors[component].columns
+    }
+}
+
+impl Model for ProductModel<'_> {
+    fn parameter_names(&self) -> Vec<String> {
+        self.parameters
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect()
+    }
+
+    fn data_names(&self) -> Vec<String> {
+        self.data.iter().map(|d| d.name().to_string()).collect()
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        self.parameters.clone()
+    }
+
+    fn data(&self) -> Vec<Data> {
+        self.data.clone()
+    }
+
+    fn expected_events(&self, parameters: &[Float], gradient: &mut [Float]) -> Option<Float> {
+        let component = &self.factors[self.extended?].component;
+        let local = component.local(parameters);
+        let mut local_gradient = vec![0.0; local.len()];
+        let expected = component
+            .model
+            .expected_events(&local, &mut local_gradient)?;
+        gradient.fill(0.0);
+        component.scatter(1.0, &local_gradient, gradient);
+        Some(expected)
+    }
+
+    fn distribution(&self, parameters: &[Float], event: &[Float], gradient: &mut [Float]) -> Float {
+        gradient.fill(0.0);
+        let mut value = 1.0;
+        for factor in &self.factors {
+            let local = factor.component.local(parameters);
+            let mut local_gradient = vec![0.0; local.len()];
+            let factor_value = factor.component.model.distribution(
+                &local,
+                &factor.event(event),
+                &mut local_gradient,
+            );
+            gradient.iter_mut().for_each(|g| *g *= factor_value);
+            factor.component.scatter(value, &local_gradient, gradient);
+            value *= factor_value;
+        }
+        value
+    }
+
+    fn log_likelihood(
+        &self,
+        parameters: &[Float],
+        event: &[Float],
+        gradient: &mut [Float],
+    ) -> Float {
+        gradient.fill(0.0);
+        let mut value = 0.0;
+        for factor in &self.factors {
+            let local = factor.component.local(parameters);
+            let mut local_gradient = vec![0.0; local.len()];
+            value += factor.component.model.log_likelihood(
+                &local,
+                &factor.event(event),
+                &mut local_gradient,
+            );
+            factor.component.scatter(1.0, &local_gradient, gradient);
+        }
+        value
+    }
+
+    fn log_likelihood_hessian(
+        &self,
+        parameters: &[Float],
+        event: &[Float],
+        gradient: &mut [Float],
+        hessian: &mut [Float],
+    ) -> Option<Float> {
+        gradient.fill(0.0);
+        hessian.fill(0.0);
+        let mut value = 0.0;
+        for factor in &self.factors {
+            let local = factor.component.local(parameters);
+            let n = local.len();
+            let mut local_gradient = vec![0.0; n];
+            let mut local_hessian = vec![0.0; n * n];
+            value += factor.component.model.log_likelihood_hessian(
+                &local,
+                &factor.event(event),
+                &mut local_gradient,
+                &mut local_hessian,
+            )?;
+            factor.component.scatter(1.0, &local_gradient, gradient);
+            factor.scatter_hessian(&local_hessian, hessian);
+        }
+        Some(value)
+    }
+
+    fn log_normalization(&self, parameters: &[Float], gradient: &mut [Float]) -> Float {
+        gradient.fill(0.0);
+        let mut value = 0.0;
+        for factor in &self.factors {
+            let local = factor.component.local(parameters);
+            let mut local_gradient = vec![0.0; local.len()];
+            value += factor
+                .component
+                .model
+                .log_normalization(&local, &mut local_gradient);
+            factor.component.scatter(1.0, &local_gradient, gradient);
+        }
+        value
+    }
+
+    fn log_normalization_hessian(
+        &self,
+        parameters: &[Float],
+        gradient: &mut [Float],
+        hessian: &mut [Float],
+    ) -> Float {
+        gradient.fill(0.0);
+        hessian.fill(0.0);
+        let mut value = 0.0;
+        for factor in &self.factors {
+            let local = factor.component.local(parameters);
+            let n = local.len();
+            let mut local_gradient = vec![0.0; n];
+            let mut local_hessian = vec![0.0; n * n];
+            value += factor.component.model.log_normalization_hessian(
+                &local,
+                &mut local_gradient,
+                &mut local_hessian,
+            );
+            factor.component.scatter(1.0, &local_gradient, gradient);
+            factor.scatter_hessian(&local_hessian, hessian);
+        }
+        value
+    }
+}
+
+/// Convolution of a one-dimensional physics model with a resolution model
+/// over the residual between measured and true values,
+/// `h(x) = integral of f(x - r) g(r) dr`, computed by adaptive quadrature for
+/// every event. The physics model vanishes outside the range of its
+/// observable and the residual is integrated over the range of the
+/// resolution's. The measured observable defaults to the physics model's and
+/// is normalized by integrating `h` over its range. Parameters are shared by
+/// name as in [`SumModel`].
+pub struct Convolution<'a> {
+    physics: Component<'a>,
+    resolution: Component<'a>,
+    parameters: Vec<Parameter>,
+    observable: Data,
+    tolerance: Float,
+}
+
+impl<'a> Convolution<'a> {
+    pub fn new(physics: &'a dyn Model, resolution: &'a dyn Model) -> Self {
+        assert!(
+            physics.data().len() == 1 && resolution.data().len() == 1,
+            "convolution needs one-dimensional models"
+        );
+        let mut parameters = Vec::new();
+        let mut component = |model: &'a dyn Model| Component {
+            model,
+            indices: model
+                .parameters()
+                .into_iter()
+                .map(|parameter| parameter_index(&mut parameters, parameter))
+                .collect(),
+        };
+        let physics = component(physics);
+        let resolution = component(resolution);
+        let observable = physics.model.data().remove(0);
+        Self {
+            physics,
+            resolution,
+            parameters,
+            observable,
+            tolerance: TOLERANCE,
+        }
+    }
+
+    /// Replaces the measured observable, for instance to extend its range
+    /// below that of the physics model by the resolution.
+    pub fn with_observable(mut self, observable: Data) -> Self {
+        self.observable = observable;
+        self
+    }
+
+    /// Relative tolerance of the integrals.
+    pub fn with_tolerance(mut self, tolerance: Float) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    fn range(model: &dyn Model) -> (Float, Float) {
+        model.data()[0].distribution().bounds()
+    }
+
+    /// Value of `h` at `x` with its gradient.
+    fn convolve(&self, parameters: &[Float], x: Float, gradient: &mut [Float]) -> Float {
+        let physics = self.physics.local(parameters);
+        let resolution = self.resolution.local(parameters);
+        let (physics_low, physics_high) = Self::range(self.physics.model);
+        let (low, high) = Self::range(self.resolution.model);
+        let (low, high) = (low.max(x - physics_high), high.min(x - physics_low));
+        gradient.fill(0.0);
+        if low >= high {
+            return 0.0;
+        }
+        let mut physics_gradient = vec![0.0; physics.len()];
+        let mut resolution_gradient = vec![0.0; resolution.len()];
+        let (value, integral_gradient) = gauss_kronrod(
+            low,
+            high,
+            parameters.len(),
+            self.tolerance,
+            |residual, g| {
+                let f = self.physics.model.distribution(
+                    &physics,
+                    &[x - residual],
+                    &mut physics_gradient,
+                );
+                let r = self.resolution.model.distribution(
+                    &resolution,
+                    &[residual],
+                    &mut resolution_gradient,
+                );
+                g.fill(0.0);
+                self.physics.scatter(r, &physics_gradient, g);
+                self.resolution.scatter(f, &resolution_gradient, g);
+                f * r
+            },
+        );
+        gradient.copy_from_slice(&integral_gradient);
+        value
+    }
+}
+
+impl Model for Convolution<'_> {
+    fn parameter_names(&self) -> Vec<String> {
+        self.parameters
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect()
+    }
+
+    fn data_names(&self) -> Vec<String> {
+        vec![self.observable.name().to_string()]
+    }
+
+    fn parameters(&self) -> Vec<Parameter> {
+        self.parameters.clone()
+    }
+
+    fn data(&self) -> Vec<Data> {
+        vec![self.observable.clone()]
+    }
+
+    fn expected_events(&self, parameters: &[Float], gradient: &mut [Float]) -> Option<Float> {
+        let local = self.physics.local(parameters);
+        let mut local_gradient = vec![0.0; local.len()];
+        let expected = self
+            .physics
+            .model
+            .expected_events(&local, &mut local_gradient)?;
+        gradient.fill(0.0);
+        self.physics.scatter(1.0, &local_gradient, gradient);
+        Some(expected)
+    }
+
+    fn distribution(&self, parameters: &[Float], event: &[Float], gradient: &mut [Float]) -> Float {
+        self.convolve(parameters, event[0], gradient)
+    }
+
+    fn log_likelihood(
+        &self,
+        parameters: &[Float],
+        event: &[Float],
+        gradient: &mut [Float],
+    ) -> Float {
+        let value = self.convolve(parameters, event[0], gradient);
+        if value <= 0.0 {
+            gradient.fill(0.0);
+            return Float::NEG_INFINITY;
+        }
+        gradient.iter_mut().for_each(|g| *g /= value);
+        value.ln()
+    }
+
+    fn log_normalization(&self, parameters: &[Float], gradient: &mut [Float]) -> Float {
+        let (low, high) = self.observable.distribution().bounds();
+        log_integral(
+            gauss_kronrod(low, high, parameters.len(), self.tolerance, |x, g| {
+                self.convolve(parameters, x, g)
+            }),
+            gradient,
+        )
+    }
+}
@@ -0,0 +1,288 @@
+use intermediate_representation::Float;
+
+use crate::matrix::{Matrix, dot};
+use crate::minimize::Objective;
+use crate::parameter::Parameter;
+
+/// External measurement of one or more parameters, entering the fit as a
+/// Gaussian penalty.
+#[derive(Debug, Clone)]
+pub enum Constraint {
+    Gaussian {
+        parameter: String,
+        mean: Float,
+        width: Float,
+    },
+    MultivariateGaussian {
+        parameters: Vec<String>,
+        mean: Vec<Float>,
+        covariance: Matrix,
+    },
+}
+
+impl Constraint {
+    pub fn gaussian(parameter: &str, mean: Float, width: Float) -> Self {
+        assert!(width > 0.0, "constraint width must be positive");
+        Self::Gaussian {
+            parameter: parameter.to_string(),
+            mean,
+            width,
+        }
+    }
+
+    pub fn multivariate_gaussian(
+        parameters: &[&str],
+        mean: Vec<Float>,
+        covariance: Matrix,
+    ) -> Self {
+        assert_eq!(parameters.len(), mean.len());
+        assert_eq!(parameters.len(), covariance.size());
+        assert!(
+            covariance.is_positive_definite(),
+            "constraint covariance must be positive definite"
+        );
+        Self::MultivariateGaussian {
+            parameters: parameters.iter().map(|p| p.to_string()).collect(),
+            mean,
+            covariance,
+        }
+    }
+}
+
+/// Deviation of a constrained parameter from its external measurement, in
+/// units of the measurement's uncertainty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintPull {
+    pub parameter: String,
+    pub mean: Float,
+    pub width: Float,
+    pub value: Float,
+    pub pull: Float,
+}
+
+/// A constraint resolved against the objective's parameter layout.
+#[derive(Debug, Clone)]
+struct Penalty {
+    indices: Vec<usize>,
+    mean: Vec<Float>,
+    widths: Vec<Float>,
+    inverse: Matrix,
+}
+
+impl Penalty {
+    fn residuals(&self, parameters: &[Float]) -> Vec<Float> {
+        self.indices
+            .iter()
+            .zip(&self.mean)
+            .map(|(&i, mean)| parameters[i] - mean)
+            .collect()
+    }
+}
+
+/// Objective with Gaussian constraints added, scaled so that a one standard
+/// deviation shift of a constrained parameter changes it by the error
+/// definition.
+pub struct Constrained<'a, O: Objective + ?Sized> {
+    objective: &'a O,
+    parameters: Vec<Parameter>,
+    penalties: Vec<Penalty>,
+}
+
+impl<'a, O: Objective + ?Sized> Constrained<'a, O> {
+    pub fn new(objective: &'a O, parameters: Vec<Parameter>) -> Self {
+        assert_eq!(
+            objective.dimension(),
+            parameters.len(),
+            "one parameter is required per objective dimension"
+        );
+        Self {
+            objective,
+            parameters,
+            penalties: Vec::new(),
+        }
+    }
+
+    pub fn with_constraint(mut self, constraint: Constraint) -> Self {
+        let (names, mean, covariance) = match constraint {
+            Constraint::Gaussian {
+                parameter,
+                mean,
+                width,
+            } => (
+                vec![parameter],
+                vec![mean],
+                Matrix::from_diagonal(&[width * width]),
+            ),
+            Constraint::MultivariateGaussian {
+                parameters,
+                mean,
+                covariance,
+            } => (parameters, mean, covariance),
+        };
+        let indices = names
+            .iter()
+            .map(|name| {
+                self.parameters
+                    .iter()
+                    .position(|p| p.name() == name)
+                    .unwrap_or_else(|| panic!("unknown parameter `{}`", name))
+            })
+            .collect();
+        self.penalties.push(Penalty {
+            indices,
+            mean,
+            widths: covariance.diagonal().iter().map(|v| v.sqrt()).collect(),
+            inverse: covariance
+                .inverse()
+                .expect("constraint covariance must be invertible"),
+        });
+        self
+    }
+
+    pub fn objective(&self) -> &O {
+        self.objective
+    }
+
+    pub fn parameters(&self) -> Vec<Parameter> {
+        self.parameters.clone()
+    }
+
+    /// Sum of the penalties' second derivatives, which do not depend on the
+    /// parameters.
+    fn penalty_hessian(&self) -> Matrix {
+        let scale = 2.0 * self.objective.error_definition();
+        let mut hessian = Matrix::zeros(self.parameters.len());
+        for penalty in &self.penalties {
+            for (a, &i) in penalty.indices.iter().enumerate() {
+                for (b, &j) in penalty.indices.iter().enumerate() {
+                    hessian[(i, j)] += scale * penalty.inverse[(a, b)];
+                }
+            }
+        }
+        hessian
+    }
+}
+
+impl<O: Objective + ?Sized> Objective for Constrained<'_, O> {
+    fn dimension(&self) -> usize {
+        self.objective.dimension()
+    }
+
+    fn error_definition(&self) -> Float {
+        self.objective.error_definition()
+    }
+
+    fn value_and_gradient(&self, parameters: &[Float]) -> (Float, Vec<Float>) {
+        let (mut value, mut gradient) = self.objective.value_and_gradient(parameters);
+        let up = self.objective.error_definition();
+        for penalty in &self.penalties {
+            let residuals = penalty.residuals(parameters);
+            let pulled = penalty.inverse.multiply_vector(&residuals);
+            value += up * dot(&residuals, &pulled);
+            for (&i, p) in penalty.indices.iter().zip(&pulled) {
+                gradient[i] += 2.0 * up * p;
+            }
+        }
+        (value, gradient)
+    }
+
+    fn hessian(&self, parameters: &[Float]) -> Option<Matrix> {
+        let mut hessian = self.objective.hessian(parameters)?;
+        hessian.add(&self.penalty_hessian());
+        Some(hessian)
+    }
+
+    fn gradient_covariance(&self, parameters: &[Float]) -> Option<Matrix> {
+        let mut covariance = self.objective.gradient_covariance(parameters)?;
+        covariance.add(&self.penalty_hessian());
+        Some(covariance)
+    }
+
+    fn constraint_pulls(&self, parameters: &[Float]) -> Vec<ConstraintPull> {
+        let mut pulls = self.objective.constraint_pulls(parameters);
+        for penalty in &self.penalties {
+            for ((&i, &mean), &width) in penalty
+                .indices
+                .iter()
+                .zip(&penalty.mean)
+                .zip(&penalty.widths)
+            {
+                pulls.push(ConstraintPull {
+                    parameter: self.parameters[i].name().to_string(),
+                    mean,
+                    width,
+                    value: parameters[i],
+                    pull: (parameters[i] - mean) / width,
+                });
+            }
+        }
+        pulls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minimize::Minimizer;
+
+    /// Two independent unit-width measurements, of `a` at 1 and `b` at 2.
+    struct Measurements;
+
+    impl Objective for Measurements {
+        fn dimension(&self) -> usize {
+            2
+        }
+
+        fn error_definition(&self) -> Float {
+            0.5
+        }
+
+        fn value_and_gradient(&self, p: &[Float]) -> (Float, Vec<Float>) {
+            let value = 0.5 * (p[0] - 1.0).powi(2) + 0.5 * (p[1] - 2.0).powi(2);
+            (value, vec![p[0] - 1.0, p[1] - 2.0])
+        }
+    }
+
+    fn parameters() -> Vec<Parameter> {
+        vec![
+            Parameter::new("a".to_string(), false),
+            Parameter::new("b".to_string(), false),
+        ]
+    }
+
+    #[test]
+    fn gaussian_constraint_combines_measurements() {
+        let constrained = Constrained::new(&Measurements, parameters())
+            .with_constraint(Constraint::gaussian("a", 3.0, 1.0));
+        let result = Minimizer::new(&constrained, constrained.parameters()).fit();
+
+        assert!((result.values[0] - 2.0).abs() < 1e-6, "{:?}", result);
+        assert!((result.values[1] - 2.0).abs() < 1e-6, "{:?}", result);
+        let errors = result.errors().unwrap();
+        assert!((errors[0] - 0.5_f64.sqrt()).abs() < 1e-4, "{:?}", errors);
+        assert!((errors[1] - 1.0).abs() < 1e-4, "{:?}", errors);
+        assert_eq!(result.pulls.len(), 1);
+        assert_eq!(result.pulls[0].parameter, "a");
+        assert!((result.pulls[0].pull + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn correlated_constraint() {
+        let mut covariance = Matrix::identity(2);
+        covariance[(0, 1)] = 0.5;
+        covariance[(1, 0)] = 0.5;
+        let constrained = Constrained::new(&Measurements, parameters()).with_constraint(
+            Constraint::multivariate_gaussian(&["a", "b"], vec![0.0, 0.0], covariance.clone()),
+        );
+        let result = Minimizer::new(&constrained, constrained.parameters()).fit();
+
+        // Combining with a unit covariance gives (I + V^-1)^-1 (x + V^-1 0).
+        let mut total = covariance.inverse().unwrap();
+        total.add(&Matrix::identity(2));
+        let expected = total.inverse().unwrap().multiply_vector(&[1.0, 2.0]);
+        for (value, expected) in result.values.iter().zip(&expected) {
+            assert!((value - expected).abs() < 1e-6, "{:?}", result);
+        }
+        assert_eq!(result.pulls.len(), 2);
+    }
+}
@@ -1,11 +1,8 @@
 use intermediate_representation::Float;
+use rand::Rng;
+use rayon::prelude::*;
 
-#[derive(Debug, Clone)]
-pub enum Distribution {
-    Uniform(Float, Float),
-    Exponential(Float, Option<[Float; 2]>),
-    Gaussian(Float, Float, Option<[Float; 2]>),
-}
+pub use crate::distribution::Distribution;
 
 #[derive(Debug, Clone)]
 pub struct Data {
@@ -20,4 +17,171 @@ impl Data {
             distribution,
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn distribution(&self) -> &Distribution {
+        &self.distribution
+    }
+}
+
+/// Events stored row by row, with one named column per observable and an
+/// optional weight per event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dataset {
+    columns: Vec<String>,
+    values: Vec<Float>,
+    weights: Option<Vec<Float>>,
+}
+
+impl Dataset {
+    pub fn new(columns: Vec<String>) -> Self {
+        assert!(!columns.is_empty(), "a dataset needs at least one column");
+        Self {
+            columns,
+            values: Vec::new(),
+            weights: None,
+        }
+    }
+
+    pub fn from_rows<const D: usize>(columns: [&str; D], rows: &[[Float; D]]) -> Self {
+        let mut dataset = Self::new(columns.iter().map(|c| c.to_string()).collect());
+        dataset.values = rows.as_flattened().to_vec();
+        dataset
+    }
+
+    /// Attaches one weight per event.
+    pub fn with_weights(mut self, weights: Vec<Float>) -> Self {
+        assert_eq!(
+            weights.len(),
+            self.len(),
+            "one weight is required per event"
+        );
+        self.weights = Some(weights);
+        self
+    }
+
+    /// Moves the named column out of the observables and uses it as the
+    /// per-event weight.
+    pub fn with_weight_column(self, name: &str) -> Self {
+        let index = self
+            .column_index(name)
+            .unwrap_or_else(|| panic!("dataset has no column `{}`", name));
+        let mut dataset = Self::new(
+            self.columns
+                .iter()
+                .filter(|c| *c != name)
+                .cloned()
+                .collect(),
+        );
+        let mut weights = Vec::with_capacity(self.len());
+        for event in self.events() {
+            weights.push(event[index]);
+            dataset.values.extend(
+                event
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != index)
+                    .map(|(_, v)| v),
+            );
+        }
+        dataset.weights = Some(weights);
+        dataset
+    }
+
+    /// Draws `events` events with each column sampled independently from the
+    /// distribution of its observable.
+    pub fn generate(data: &[Data], rng: &mut impl Rng, events: usize) -> Self {
+        let mut dataset = Self::new(data.iter().map(|d| d.name().to_string()).collect());
+        let mut event = vec![0.0; data.len()];
+        for _ in 0..events {
+            for (x, d) in event.iter_mut().zip(data) {
+                *x = d.distribution().sample(rng);
+            }
+            dataset.push(&event);
+        }
+        dataset
+    }
+
+    pub fn push(&mut self, event: &[Float]) {
+        self.push_weighted(event, 1.0);
+    }
+
+    /// Adds an event with the given weight. Events already in an unweighted
+    /// dataset are given a weight of one.
+    pub fn push_weighted(&mut self, event: &[Float], weight: Float) {
+        assert_eq!(event.len(), self.columns.len());
+        if weight != 1.0 && self.weights.is_none() {
+            self.weights = Some(vec![1.0; self.len()]);
+        }
+        if let Some(weights) = &mut self.weights {
+            weights.push(weight);
+        }
+        self.values.extend_from_slice(event);
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len() / self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn is_weighted(&self) -> bool {
+        self.weights.is_some()
+    }
+
+    pub fn weights(&self) -> Option<&[Float]> {
+        self.weights.as_deref()
+    }
+
+    pub fn weight(&self, index: usize) -> Float {
+        self.weights.as_ref().map_or(1.0, |w| w[index])
+    }
+
+    /// Effective number of events, the number of events when unweighted.
+    pub fn sum_of_weights(&self) -> Float {
+        self.weights
+            .as_ref()
+            .map_or(self.len() as Float, |w| w.iter().sum())
+    }
+
+    pub fn sum_of_squared_weights(&self) -> Float {
+        self.weights
+            .as_ref()
+            .map_or(self.len() as Float, |w| w.iter().map(|w| w * w).sum())
+    }
+
+    pub fn event(&self, index: usize) -> &[Float] {
+        let width = self.columns.len();
+        &self.values[index * width..(index + 1) * width]
+    }
+
+    pub fn events(&self) -> std::slice::ChunksExact<'_, Float> {
+        self.values.chunks_exact(self.columns.len())
+    }
+
+    pub fn par_events(&self) -> rayon::slice::ChunksExact<'_, Float> {
+        self.values.par_chunks_exact(self.columns.len())
+    }
+
+    /// Events in parallel, each paired with its weight.
+    pub fn par_weighted_events(
+        &self,
+    ) -> impl IndexedParallelIterator<Item = (&[Float], Float)> + '_ {
+        self.par_events()
+            .enumerate()
+            .map(|(i, event)| (event, self.weight(i)))
+    }
 }
@@ -0,0 +1,419 @@
+use intermediate_representation::special::{
+    beta_regularized, gamma_p, gamma_q, ln_gamma, normal_cdf, normal_quantile,
+};
+use intermediate_representation::{Float, FloatConsts};
+use rand::Rng;
+
+/// Probability distribution of an observable. The optional range truncates
+/// the distribution, which is then renormalized over it.
+#[derive(Debug, Clone)]
+pub enum Distribution {
+    Uniform(Float, Float),
+    /// Rate.
+    Exponential(Float, Option<[Float; 2]>),
+    /// Mean and standard deviation.
+    Gaussian(Float, Float, Option<[Float; 2]>),
+    /// Mean of a distribution over the non-negative integers.
+    Poisson(Float, Option<[Float; 2]>),
+    /// Shape and scale.
+    Gamma(Float, Float, Option<[Float; 2]>),
+    /// The two shape parameters, on `[0, 1]`.
+    Beta(Float, Float, Option<[Float; 2]>),
+    /// Mean and standard deviation of the logarithm.
+    LogNormal(Float, Float, Option<[Float; 2]>),
+    /// Degrees of freedom.
+    StudentT(Float, Option<[Float; 2]>),
+    /// Degrees of freedom.
+    ChiSquare(Float, Option<[Float; 2]>),
+    /// Location and scale.
+    Cauchy(Float, Float, Option<[Float; 2]>),
+}
+
+const MAX_ITERATIONS: usize = 200;
+
+impl Distribution {
+    /// Interval the observable is restricted to, if bounded.
+    pub fn range(&self) -> Option<(Float, Float)> {
+        match self {
+            Distribution::Uniform(low, high) => Some((*low, *high)),
+            Distribution::Exponential(_, range)
+            | Distribution::Gaussian(_, _, range)
+            | Distribution::Poisson(_, range)
+            | Distribution::Gamma(_, _, range)
+            | Distribution::Beta(_, _, range)
+            | Distribution::LogNormal(_, _, range)
+            | Distribution::StudentT(_, range)
+            | Distribution::ChiSquare(_, range)
+            | Distribution::Cauchy(_, _, range) => range.map(|[low, high]| (low, high)),
+        }
+    }
+
+    /// Interval on which the untruncated distribution is defined.
+    pub fn support(&self) -> (Float, Float) {
+        match self {
+            Distribution::Uniform(low, high) => (*low, *high),
+            Distribution::Exponential(..)
+            | Distribution::Poisson(..)
+            | Distribution::Gamma(..)
+            | Distribution::LogNormal(..)
+            | Distribution::ChiSquare(..) => (0.0, Float::INFINITY),
+            Distribution::Beta(..) => (0.0, 1.0),
+            Distribution::Gaussian(..) | Distribution::StudentT(..) | Distribution::Cauchy(..) => {
+                (Float::NEG_INFINITY, Float::INFINITY)
+            }
+        }
+    }
+
+    /// The support intersected with the truncation range.
+    pub fn bounds(&self) -> (Float, Float) {
+        let (low, high) = self.support();
+        match self.range() {
+            Some((a, b)) => (low.max(a), high.min(b)),
+            None => (low, high),
+        }
+    }
+
+    pub fn is_discrete(&self) -> bool {
+        matches!(self, Distribution::Poisson(..))
+    }
+
+    /// Probability density, or probability mass for discrete distributions.
+    pub fn pdf(&self, x: Float) -> Float {
+        self.log_pdf(x).exp()
+    }
+
+    pub fn log_pdf(&self, x: Float) -> Float {
+        let (low, high) = self.bounds();
+        if !(x >= low && x <= high) || (self.is_discrete() && x.fract() != 0.0) {
+            return Float::NEG_INFINITY;
+        }
+        match self.range() {
+            Some(_) => self.raw_log_pdf(x) - self.truncation().1.ln(),
+            None => self.raw_log_pdf(x),
+        }
+    }
+
+    pub fn cdf(&self, x: Float) -> Float {
+        let (low, high) = self.bounds();
+        if x < low {
+            return 0.0;
+        }
+        if x >= high {
+            return 1.0;
+        }
+        match self.range() {
+            Some(_) => {
+                let (below, mass) = self.truncation();
+                ((self.raw_cdf(x) - below) / mass).clamp(0.0, 1.0)
+            }
+            None => self.raw_cdf(x),
+        }
+    }
+
+    /// Smallest `x` with `cdf(x) >= p`.
+    pub fn quantile(&self, p: Float) -> Float {
+        let (low, high) = self.bounds();
+        if p <= 0.0 {
+            return low;
+        }
+        if p >= 1.0 {
+            return high;
+        }
+        let p = match self.range() {
+            Some(_) => {
+                let (below, mass) = self.truncation();
+                below + p * mass
+            }
+            None => p,
+        };
+        self.raw_quantile(p).clamp(low, high)
+    }
+
+    /// Draws a value by inverting the (truncated) cumulative distribution.
+    pub fn sample(&self, rng: &mut impl Rng) -> Float {
+        self.quantile(rng.random())
+    }
+
+    /// Probability below the truncation range and probability inside it.
+    fn truncation(&self) -> (Float, Float) {
+        let (low, high) = self.bounds();
+        let below = if self.is_discrete() {
+            self.raw_cdf(low.ceil() - 1.0)
+        } else {
+            self.raw_cdf(low)
+        };
+        (below, self.raw_cdf(high) - below)
+    }
+
+    fn raw_log_pdf(&self, x: Float) -> Float {
+        match *self {
+            Distribution::Uniform(low, high) => -(high - low).ln(),
+            Distribution::Exponential(rate, _) => rate.ln() - rate * x,
+            Distribution::Gaussian(mean, sigma, _) => {
+                let z = (x - mean) / sigma;
+                -0.5 * z * z - (sigma * (2.0 * Float::PI).sqrt()).ln()
+            }
+            Distribution::Poisson(mean, _) => x * mean.ln() - mean - ln_gamma(x + 1.0),
+            Distribution::Gamma(shape, scale, _) => {
+                let power = if shape == 1.0 {
+                    0.0
+                } else {
+                    (shape - 1.0) * x.ln()
+                };
+                power - x / scale - ln_gamma(shape) - shape * scale.ln()
+            }
+            Distribution::Beta(a, b, _) => {
+                let left = if a == 1.0 { 0.0 } else { (a - 1.0) * x.ln() };
+                let right = if b == 1.0 {
+                    0.0
+                } else {
+                    (b - 1.0) * (1.0 - x).ln()
+                };
+                left + right + ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b)
+            }
+            Distribution::LogNormal(mean, sigma, _) => {
+                let z = (x.ln() - mean) / sigma;
+                -0.5 * z * z - (x * sigma * (2.0 * Float::PI).sqrt()).ln()
+            }
+            Distribution::StudentT(nu, _) => {
+                ln_gamma(0.5 * (nu + 1.0))
+                    - ln_gamma(0.5 * nu)
+                    - 0.5 * (nu * Float::PI).ln()
+                    - 0.5 * (nu + 1.0) * (x * x / nu).ln_1p()
+            }
+            Distribution::ChiSquare(k, _) => Distribution::Gamma(0.5 * k, 2.0, None).raw_log_pdf(x),
+            Distribution::Cauchy(location, scale, _) => {
+                let z = (x - location) / scale;
+                -(Float::PI * scale * (1.0 + z * z)).ln()
+            }
+        }
+    }
+
+    /// Untruncated `P(X <= x)`.
+    fn raw_cdf(&self, x: Float) -> Float {
+        let (low, high) = self.support();
+        if x < low {
+            return 0.0;
+        }
+        if x >= high {
+            return 1.0;
+        }
+        match *self {
+            Distribution::Uniform(low, high) => (x - low) / (high - low),
+            Distribution::Exponential(rate, _) => -(-rate * x).exp_m1(),
+            Distribution::Gaussian(mean, sigma, _) => normal_cdf((x - mean) / sigma),
+            Distribution::Poisson(mean, _) => gamma_q(x.floor() + 1.0, mean),
+            Distribution::Gamma(shape, scale, _) => gamma_p(shape, x / scale),
+            Distribution::Beta(a, b, _) => beta_regularized(a, b, x),
+            Distribution::LogNormal(mean, sigma, _) => normal_cdf((x.ln() - mean) / sigma),
+            Distribution::StudentT(nu, _) => {
+                let t2 = x * x;
+                let tail = if t2 < nu {
+                    0.5 - 0.5 * beta_regularized(0.5, 0.5 * nu, t2 / (nu + t2))
+                } else {
+                    0.5 * beta_regularized(0.5 * nu, 0.5, nu / (nu + t2))
+                };
+                if x < 0.0 { tail } else { 1.0 - tail }
+            }
+            Distribution::ChiSquare(k, _) => gamma_p(0.5 * k, 0.5 * x),
+            Distribution::Cauchy(location, scale, _) => {
+                0.5 + ((x - location) / scale).atan() / Float::PI
+            }
+        }
+    }
+
+    fn raw_quantile(&self, p: Float) -> Float {
+        match *self {
+            Distribution::Uniform(low, high) => low + p * (high - low),
+            Distribution::Exponential(rate, _) => -(-p).ln_1p() / rate,
+            Distribution::Gaussian(mean, sigma, _) => mean + sigma * normal_quantile(p),
+            Distribution::LogNormal(mean, sigma, _) => (mean + sigma * normal_quantile(p)).exp(),
+            Distribution::Cauchy(location, scale, _) => {
+                location + scale * (Float::PI * (p - 0.5)).tan()
+            }
+            Distribution::Poisson(mean, _) => {
+                // Allow for the rounding of a probability obtained from `cdf`.
+                let p = p - 8.0 * Float::EPSILON;
+                let guess = mean + mean.sqrt() * normal_quantile(p);
+                let mut k = guess.floor().max(0.0);
+                while k > 0.0 && self.raw_cdf(k - 1.0) >= p {
+                    k -= 1.0;
+                }
+                while self.raw_cdf(k) < p {
+                    k += 1.0;
+                }
+                k
+            }
+            Distribution::Gamma(..)
+            | Distribution::Beta(..)
+            | Distribution::StudentT(..)
+            | Distribution::ChiSquare(..) => self.invert(p),
+        }
+    }
+
+    /// Solves `raw_cdf(x) = p` by Newton's method safeguarded with bisection.
+    fn invert(&self, p: Float) -> Float {
+        let (mut low, mut high) = self.support();
+        let mut step = 1.0;
+        if low.is_infinite() {
+            low = -step;
+            while self.raw_cdf(low) > p {
+                low -= step;
+                step *= 2.0;
+            }
+        }
+        step = 1.0;
+        if high.is_infinite() {
+            high = low.max(0.0) + step;
+            while self.raw_cdf(high) < p {
+                high += step;
+                step *= 2.0;
+            }
+        }
+
+        let mut x = 0.5 * (low + high);
+        for _ in 0..MAX_ITERATIONS {
+            let residual = self.raw_cdf(x) - p;
+            if residual == 0.0 {
+                return x;
+            }
+            if residual > 0.0 {
+                high = x;
+            } else {
+                low = x;
+            }
+            let newton = x - residual / self.raw_log_pdf(x).exp();
+            let next = if newton > low && newton < high {
+                newton
+            } else {
+                0.5 * (low + high)
+            };
+            if (next - x).abs() <= 1e-14 * x.abs().max(1e-300) {
+                return next;
+            }
+            x = next;
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration::gauss_legendre;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn continuous() -> Vec<Distribution> {
+        vec![
+            Distribution::Uniform(-1.0, 3.0),
+            Distribution::Exponential(0.7, None),
+            Distribution::Exponential(0.7, Some([1.0, 4.0])),
+            Distribution::Gaussian(1.0, 2.0, None),
+            Distribution::Gaussian(1.0, 2.0, Some([-2.0, 0.5])),
+            Distribution::Gamma(2.5, 1.5, None),
+            Distribution::Beta(2.0, 5.0, None),
+            Distribution::Beta(0.5, 0.5, Some([0.2, 0.9])),
+            Distribution::LogNormal(0.3, 0.6, None),
+            Distribution::StudentT(3.0, None),
+            Distribution::StudentT(5.0, Some([-1.0, 4.0])),
+            Distribution::ChiSquare(4.0, None),
+            Distribution::Cauchy(0.5, 2.0, Some([-10.0, 10.0])),
+        ]
+    }
+
+    #[test]
+    fn reference_values() {
+        let close = |a: Float, b: Float| (a - b).abs() < 1e-12;
+        assert!(close(
+            Distribution::Gamma(2.0, 1.0, None).cdf(1.0),
+            0.264_241_117_657_115_3
+        ));
+        assert!(close(
+            Distribution::StudentT(3.0, None).cdf(1.0),
+            0.804_498_890_522_114_8
+        ));
+        assert!(close(
+            Distribution::ChiSquare(4.0, None).cdf(2.0),
+            0.264_241_117_657_115_3
+        ));
+        assert!(close(Distribution::Beta(2.0, 3.0, None).cdf(0.4), 0.5248));
+        assert!(close(
+            Distribution::Poisson(3.0, None).cdf(2.0),
+            0.423_190_081_126_843_5
+        ));
+        assert!(close(
+            Distribution::Poisson(3.0, None).pdf(2.0),
+            0.224_041_807_655_387_8
+        ));
+        assert!(close(Distribution::Cauchy(0.0, 1.0, None).cdf(1.0), 0.75));
+        assert!(close(Distribution::LogNormal(0.0, 1.0, None).cdf(1.0), 0.5));
+        assert!(close(
+            Distribution::Gaussian(0.0, 1.0, None).pdf(1.0),
+            0.241_970_724_519_143_37
+        ));
+    }
+
+    #[test]
+    fn quantile_inverts_cdf() {
+        for distribution in continuous() {
+            for p in [0.01, 0.2, 0.5, 0.77, 0.99] {
+                let x = distribution.quantile(p);
+                assert!(
+                    (distribution.cdf(x) - p).abs() < 1e-10,
+                    "{:?} {} {}",
+                    distribution,
+                    p,
+                    x
+                );
+            }
+        }
+        let poisson = Distribution::Poisson(4.5, Some([2.0, 9.0]));
+        for k in 2..=9 {
+            let k = k as Float;
+            assert_eq!(poisson.quantile(poisson.cdf(k)), k);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_cdf() {
+        for distribution in continuous() {
+            let low = distribution.quantile(0.05);
+            let high = distribution.quantile(0.95);
+            let steps = 200;
+            let width = (high - low) / steps as Float;
+            let integral: Float = (0..steps)
+                .map(|i| {
+                    let a = low + i as Float * width;
+                    gauss_legendre(a, a + width, 0, |x, _| distribution.pdf(x)).0
+                })
+                .sum();
+            assert!(
+                (integral - 0.9).abs() < 1e-8,
+                "{:?} {}",
+                distribution,
+                integral
+            );
+        }
+        let poisson = Distribution::Poisson(2.0, Some([1.0, 5.0]));
+        let total: Float = (0..10).map(|k| poisson.pdf(k as Float)).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+        assert_eq!(poisson.pdf(1.5), 0.0);
+    }
+
+    #[test]
+    fn sampling_honors_truncation() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let distribution = Distribution::Gaussian(0.0, 1.0, Some([1.0, 2.0]));
+        let samples: Vec<Float> = (0..20000).map(|_| distribution.sample(&mut rng)).collect();
+        assert!(samples.iter().all(|x| (1.0..=2.0).contains(x)));
+        let mean = samples.iter().sum::<Float>() / samples.len() as Float;
+        assert!((mean - 1.383_184_7).abs() < 0.01, "{}", mean);
+
+        let poisson = Distribution::Poisson(3.0, None);
+        let samples: Vec<Float> = (0..20000).map(|_| poisson.sample(&mut rng)).collect();
+        assert!(samples.iter().all(|k| k.fract() == 0.0));
+        let mean = samples.iter().sum::<Float>() / samples.len() as Float;
+        assert!((mean - 3.0).abs() < 0.05, "{}", mean);
+    }
+}
@@ -1 +1,385 @@
-pub struct Generation {}
+use intermediate_representation::Float;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::data::Dataset;
+use crate::likelihood::NegativeLogLikelihood;
+use crate::minimize::{FitStatus, Migrad, Minimizer};
+use crate::model::Model;
+
+/// Toy Monte Carlo study: pseudo-datasets are sampled at known true parameter
+/// values and refitted, so the spread of the fitted values and their pulls
+/// can be compared with the reported errors.
+#[derive(Debug, Clone)]
+pub struct Generation {
+    toys: usize,
+    seed: u64,
+    migrad: Migrad,
+}
+
+/// Outcome of the fit to one pseudo-dataset.
+#[derive(Debug, Clone)]
+pub struct ToyFit {
+    pub seed: u64,
+    pub events: usize,
+    pub values: Vec<Float>,
+    /// Parabolic errors, `None` when HESSE failed.
+    pub errors: Option<Vec<Float>>,
+    pub minimum: Float,
+    pub status: FitStatus,
+}
+
+impl ToyFit {
+    pub fn is_valid(&self) -> bool {
+        self.status == FitStatus::Converged && self.errors.is_some()
+    }
+}
+
+/// Mean and width of a sample, with their statistical uncertainties.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: Float,
+    pub mean_error: Float,
+    pub width: Float,
+    pub width_error: Float,
+}
+
+impl Summary {
+    pub fn of(values: &[Float]) -> Self {
+        let count = values.len();
+        let n = count as Float;
+        let mean = values.iter().sum::<Float>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<Float>() / (n - 1.0);
+        let width = variance.sqrt();
+        Self {
+            count,
+            mean,
+            mean_error: width / n.sqrt(),
+            width,
+            width_error: width / (2.0 * (n - 1.0)).sqrt(),
+        }
+    }
+}
+
+/// Fits of all pseudo-datasets of a study, with summaries over the valid ones.
+#[derive(Debug, Clone)]
+pub struct ToyResults {
+    pub parameter_names: Vec<String>,
+    pub truth: Vec<Float>,
+    pub fits: Vec<ToyFit>,
+}
+
+impl ToyResults {
+    pub fn valid(&self) -> impl Iterator<Item = &ToyFit> {
+        self.fits.iter().filter(|fit| fit.is_valid())
+    }
+
+    /// Fraction of toys whose fit converged with a valid covariance.
+    pub fn success_rate(&self) -> Float {
+        self.valid().count() as Float / self.fits.len() as Float
+    }
+
+    fn index(&self, parameter: &str) -> usize {
+        self.parameter_names
+            .iter()
+            .position(|name| name == parameter)
+            .unwrap_or_else(|| panic!("unknown parameter `{}`", parameter))
+    }
+
+    pub fn values(&self, parameter: &str) -> Vec<Float> {
+        let index = self.index(parameter);
+        self.valid().map(|fit| fit.values[index]).collect()
+    }
+
+    pub fn errors(&self, parameter: &str) -> Vec<Float> {
+        let index = self.index(parameter);
+        self.valid()
+            .map(|fit| fit.errors.as_ref().unwrap()[index])
+            .collect()
+    }
+
+    /// `(fitted - true) / error` for each valid toy.
+    pub fn pulls(&self, parameter: &str) -> Vec<Float> {
+        let index = self.index(parameter);
+        self.valid()
+            .map(|fit| {
+                (fit.values[index] - self.truth[index]) / fit.errors.as_ref().unwrap()[index]
+            })
+            .collect()
+    }
+
+    pub fn value_summary(&self, parameter: &str) -> Summary {
+        Summary::of(&self.values(parameter))
+    }
+
+    pub fn error_summary(&self, parameter: &str) -> Summary {
+        Summary::of(&self.errors(parameter))
+    }
+
+    /// A mean compatible with zero and a width compatible with one indicate
+    /// an unbiased fit with correct errors.
+    pub fn pull_summary(&self, parameter: &str) -> Summary {
+        Summary::of(&self.pulls(parameter))
+    }
+
+    /// Fraction of valid toys whose one-sigma interval contains the true value.
+    pub fn coverage(&self, parameter: &str) -> Float {
+        let pulls = self.pulls(parameter);
+        pulls.iter().filter(|p| p.abs() <= 1.0).count() as Float / pulls.len() as Float
+    }
+}
+
+impl Generation {
+    pub fn new(toys: usize) -> Self {
+        Self {
+            toys,
+            seed: 0,
+            migrad: Migrad::new(),
+        }
+    }
+
+    /// Toy `i` draws from an RNG seeded with `seed + i`, so any toy can be
+    /// reproduced on its own.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_migrad(mut self, migrad: Migrad) -> Self {
+        self.migrad = migrad;
+        self
+    }
+
+    /// Samples each pseudo-dataset with `sample` and fits `model` to it in
+    /// parallel, starting from `truth`. Parameters fixed in the model stay
+    /// fixed at their true values.
+    pub fn run<M: Model + ?Sized>(
+        &self,
+        model: &M,
+        truth: &[Float],
+        sample: impl Fn(&mut StdRng) -> Dataset + Sync,
+    ) -> ToyResults {
+        let mut parameters = model.parameters();
+        assert_eq!(
+            parameters.len(),
+            truth.len(),
+            "one true value is required per parameter"
+        );
+        for (parameter, value) in parameters.iter_mut().zip(truth) {
+            parameter.set_value(*value);
+        }
+
+        let fits = (0..self.toys as u64)
+            .into_par_iter()
+            .map(|toy| {
+                let seed = self.seed.wrapping_add(toy);
+                let data = sample(&mut StdRng::seed_from_u64(seed));
+                let nll = NegativeLogLikelihood::new(model, &data);
+                let result = Minimizer::new(&nll, parameters.clone())
+                    .with_migrad(self.migrad.clone())
+                    .fit();
+                ToyFit {
+                    seed,
+                    events: data.len(),
+                    errors: result.errors(),
+                    values: result.values,
+                    minimum: result.minimum,
+                    status: result.status,
+                }
+            })
+            .collect();
+
+        ToyResults {
+            parameter_names: model.parameter_names(),
+            truth: truth.to_vec(),
+            fits,
+        }
+    }
+}
+
+/// How the [`Generator`] draws events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Uniform points in the observable box, kept with probability
+    /// `f(x) / envelope`.
+    AcceptReject,
+    /// Inversion of the cumulative distribution tabulated on a grid, for
+    /// one-dimensional models.
+    InverseCdf,
+}
+
+/// Draws events from a model's distribution at fixed parameter values within
+/// a box of observable ranges.
+pub struct Generator<'a, M: Model + ?Sized> {
+    model: &'a M,
+    parameters: Vec<Float>,
+    ranges: Vec<(Float, Float)>,
+    method: Method,
+    envelope: Float,
+    /// Grid points and normalized cumulative distribution at each of them.
+    table: Option<(Vec<Float>, Vec<Float>)>,
+}
+
+/// Safety factor applied to the largest density found while estimating the
+/// envelope.
+const ENVELOPE_MARGIN: Float = 1.2;
+const ENVELOPE_TRIALS: usize = 10000;
+const TABLE_POINTS: usize = 2000;
+
+impl<'a, M: Model + ?Sized> Generator<'a, M> {
+    /// Generator over the given observable ranges, in the model's data order.
+    /// One-dimensional models default to the inverse CDF.
+    pub fn new(model: &'a M, parameters: &[Float], ranges: &[(Float, Float)]) -> Self {
+        assert_eq!(parameters.len(), model.parameter_names().len());
+        assert_eq!(
+            ranges.len(),
+            model.data_names().len(),
+            "one range is required per observable"
+        );
+        assert!(ranges.iter().all(|(low, high)| low < high));
+        let method = if ranges.len() == 1 {
+            Method::InverseCdf
+        } else {
+            Method::AcceptReject
+        };
+        Self {
+            model,
+            parameters: parameters.to_vec(),
+            ranges: ranges.to_vec(),
+            method,
+            envelope: 0.0,
+            table: None,
+        }
+        .prepare()
+    }
+
+    /// Generator at the model's own parameter values, over the ranges of its
+    /// observables' distributions.
+    pub fn from_model(model: &'a M) -> Self {
+        let ranges: Vec<(Float, Float)> = model
+            .data()
+            .iter()
+            .map(|data| {
+                let (low, high) = data.distribution().bounds();
+                assert!(
+                    low.is_finite() && high.is_finite(),
+                    "observable `{}` has no finite range",
+                    data.name()
+                );
+                (low, high)
+            })
+            .collect();
+        Self::new(model, &model.parameter_values(), &ranges)
+    }
+
+    pub fn with_method(mut self, method: Method) -> Self {
+        assert!(
+            method != Method::InverseCdf || self.ranges.len() == 1,
+            "the inverse CDF method needs a one-dimensional model"
+        );
+        self.method = method;
+        self.prepare()
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    pub fn envelope(&self) -> Float {
+        self.envelope
+    }
+
+    fn density(&self, event: &[Float], gradient: &mut [Float]) -> Float {
+        self.model.distribution(&self.parameters, event, gradient)
+    }
+
+    /// Estimates the envelope from the largest density at random points, or
+    /// tabulates the cumulative distribution, depending on the method.
+    fn prepare(mut self) -> Self {
+        let mut gradient = vec![0.0; self.parameters.len()];
+        match self.method {
+            Method::AcceptReject => {
+                let mut rng = StdRng::seed_from_u64(0);
+                let mut event = vec![0.0; self.ranges.len()];
+                let mut maximum: Float = 0.0;
+                for _ in 0..ENVELOPE_TRIALS {
+                    self.uniform_point(&mut rng, &mut event);
+                    maximum = maximum.max(self.density(&event, &mut gradient));
+                }
+                assert!(maximum > 0.0, "the distribution vanishes over the ranges");
+                self.envelope = ENVELOPE_MARGIN * maximum;
+            }
+            Method::InverseCdf => {
+                let (low, high) = self.ranges[0];
+                let step = (high - low) / (TABLE_POINTS - 1) as Float;
+                let points: Vec<Float> =
+                    (0..TABLE_POINTS).map(|i| low + i as Float * step).collect();
+                let values: Vec<Float> = points
+                    .iter()
+                    .map(|x| self.density(&[*x], &mut gradient).max(0.0))
+                    .collect();
+                let mut cumulative = vec![0.0; TABLE_POINTS];
+                for i in 1..TABLE_POINTS {
+                    cumulative[i] = cumulative[i - 1] + 0.5 * step * (values[i - 1] + values[i]);
+                }
+                let total = cumulative[TABLE_POINTS - 1];
+                assert!(total > 0.0, "the distribution vanishes over the range");
+                cumulative.iter_mut().for_each(|c| *c /= total);
+                self.table = Some((points, cumulative));
+            }
+        }
+        self
+    }
+
+    fn uniform_point(&self, rng: &mut impl Rng, event: &mut [Float]) {
+        for (x, (low, high)) in event.iter_mut().zip(&self.ranges) {
+            *x = low + (high - low) * rng.random::<Float>();
+        }
+    }
+
+    /// Draws `events` events. If accept-reject finds a density above the
+    /// envelope, the envelope is raised and the sample restarted.
+    pub fn generate(&self, rng: &mut impl Rng, events: usize) -> Dataset {
+        let mut dataset = Dataset::new(self.model.data_names());
+        match self.method {
+            Method::AcceptReject => {
+                let mut gradient = vec![0.0; self.parameters.len()];
+                let mut event = vec![0.0; self.ranges.len()];
+                let mut envelope = self.envelope;
+                while dataset.len() < events {
+                    self.uniform_point(rng, &mut event);
+                    let value = self.density(&event, &mut gradient);
+                    if value > envelope {
+                        envelope = ENVELOPE_MARGIN * value;
+                        dataset = Dataset::new(self.model.data_names());
+                        continue;
+                    }
+                    if envelope * rng.random::<Float>() < value {
+                        dataset.push(&event);
+                    }
+                }
+            }
+            Method::InverseCdf => {
+                let (points, cumulative) = self.table.as_ref().unwrap();
+                for _ in 0..events {
+                    let u: Float = rng.random();
+                    let upper = cumulative
+                        .partition_point(|&c| c < u)
+                        .clamp(1, points.len() - 1);
+                    let (c0, c1) = (cumulative[upper - 1], cumulative[upper]);
+                    let fraction = if c1 > c0 { (u - c0) / (c1 - c0) } else { 0.5 };
+                    dataset.push(&[
+                        points[upper - 1] + fraction * (points[upper] - points[upper - 1])
+                    ]);
+                }
+            }
+        }
+        dataset
+    }
+
+    pub fn generate_seeded(&self, seed: u64, events: usize) -> Dataset {
+        self.generate(&mut StdRng::seed_from_u64(seed), events)
+    }
+}
@@ -0,0 +1,150 @@
+use intermediate_representation::Float;
+
+use crate::data::Dataset;
+
+/// One-dimensional histogram of a named observable.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    column: String,
+    edges: Vec<Float>,
+    counts: Vec<Float>,
+    squared_weights: Vec<Float>,
+}
+
+impl Histogram {
+    pub fn new(column: &str, edges: Vec<Float>) -> Self {
+        assert!(edges.len() >= 2, "a histogram needs at least one bin");
+        assert!(
+            edges.windows(2).all(|w| w[0] < w[1]),
+            "bin edges must be strictly increasing"
+        );
+        let bins = edges.len() - 1;
+        Self {
+            column: column.to_string(),
+            edges,
+            counts: vec![0.0; bins],
+            squared_weights: vec![0.0; bins],
+        }
+    }
+
+    pub fn uniform(column: &str, bins: usize, low: Float, high: Float) -> Self {
+        let width = (high - low) / bins as Float;
+        let edges = (0..=bins).map(|i| low + i as Float * width).collect();
+        Self::new(column, edges)
+    }
+
+    /// Histogram of one column of `dataset`, filled with the event weights.
+    pub fn from_dataset(dataset: &Dataset, column: &str, edges: Vec<Float>) -> Self {
+        let mut histogram = Self::new(column, edges);
+        histogram.fill_dataset(dataset);
+        histogram
+    }
+
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    pub fn bins(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn edges(&self) -> &[Float] {
+        &self.edges
+    }
+
+    pub fn counts(&self) -> &[Float] {
+        &self.counts
+    }
+
+    /// Sum of squared weights in each bin, the variance of its content.
+    pub fn squared_weights(&self) -> &[Float] {
+        &self.squared_weights
+    }
+
+    /// Whether the variances differ from the contents, as they do once events
+    /// with weights other than one have been filled.
+    pub fn is_weighted(&self) -> bool {
+        self.squared_weights != self.counts
+    }
+
+    /// Replaces the contents with unweighted counts.
+    pub fn set_counts(&mut self, counts: Vec<Float>) {
+        assert_eq!(counts.len(), self.bins());
+        self.squared_weights = counts.clone();
+        self.counts = counts;
+    }
+
+    pub fn bin_range(&self, bin: usize) -> (Float, Float) {
+        (self.edges[bin], self.edges[bin + 1])
+    }
+
+    pub fn center(&self, bin: usize) -> Float {
+        0.5 * (self.edges[bin] + self.edges[bin + 1])
+    }
+
+    pub fn width(&self, bin: usize) -> Float {
+        self.edges[bin + 1] - self.edges[bin]
+    }
+
+    pub fn total(&self) -> Float {
+        self.counts.iter().sum()
+    }
+
+    /// Bin containing `value`, or `None` if it is outside the histogram.
+    pub fn find_bin(&self, value: Float) -> Option<usize> {
+        if !(value >= self.edges[0] && value < self.edges[self.edges.len() - 1]) {
+            return None;
+        }
+        Some(self.edges.partition_point(|&edge| edge <= value) - 1)
+    }
+
+    pub fn fill(&mut self, value: Float) {
+        self.fill_weighted(value, 1.0);
+    }
+
+    pub fn fill_weighted(&mut self, value: Float, weight: Float) {
+        if let Some(bin) = self.find_bin(value) {
+            self.counts[bin] += weight;
+            self.squared_weights[bin] += weight * weight;
+        }
+    }
+
+    pub fn fill_dataset(&mut self, dataset: &Dataset) {
+        let column = dataset
+            .column_index(&self.column)
+            .unwrap_or_else(|| panic!("dataset has no column `{}`", self.column));
+        for (i, event) in dataset.events().enumerate() {
+            self.fill_weighted(event[column], dataset.weight(i));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_finds_bins() {
+        let mut histogram = Histogram::uniform("x", 4, 0.0, 2.0);
+        for value in [-0.1, 0.0, 0.49, 0.5, 1.2, 1.99, 2.0] {
+            histogram.fill(value);
+        }
+        assert_eq!(histogram.counts(), &[2.0, 1.0, 1.0, 1.0]);
+        assert!(!histogram.is_weighted());
+        assert_eq!(histogram.find_bin(Float::NAN), None);
+        assert_eq!(histogram.center(1), 0.75);
+    }
+
+    #[test]
+    fn fill_dataset_uses_weights() {
+        let mut dataset = Dataset::new(vec!["x".to_string(), "y".to_string()]);
+        dataset.push(&[0.2, 5.0]);
+        dataset.push_weighted(&[0.3, 5.0], 0.5);
+        dataset.push_weighted(&[1.5, 5.0], 2.5);
+        let histogram = Histogram::from_dataset(&dataset, "x", vec![0.0, 1.0, 2.0]);
+        assert_eq!(histogram.counts(), &[1.5, 2.5]);
+        assert_eq!(histogram.squared_weights(), &[1.25, 6.25]);
+        assert_eq!(histogram.total(), dataset.sum_of_weights());
+        assert!(histogram.is_weighted());
+    }
+}
@@ -0,0 +1,375 @@
+use intermediate_representation::Float;
+
+const GAUSS_LEGENDRE_NODES: [Float; 5] = [
+    0.0,
+    -0.538_469_310_105_683_1,
+    0.538_469_310_105_683_1,
+    -0.906_179_845_938_664,
+    0.906_179_845_938_664,
+];
+const GAUSS_LEGENDRE_WEIGHTS: [Float; 5] = [
+    0.568_888_888_888_888_9,
+    0.478_628_670_499_366_5,
+    0.478_628_670_499_366_5,
+    0.236_926_885_056_189_1,
+    0.236_926_885_056_189_1,
+];
+
+/// Integrates `f` and its gradient over `[low, high]` with five-point
+/// Gauss-Legendre quadrature. `f` returns the integrand's value and writes its
+/// gradient into the provided buffer.
+pub fn gauss_legendre(
+    low: Float,
+    high: Float,
+    dimension: usize,
+    mut f: impl FnMut(Float, &mut [Float]) -> Float,
+) -> (Float, Vec<Float>) {
+    let half = 0.5 * (high - low);
+    let middle = 0.5 * (high + low);
+    let mut value = 0.0;
+    let mut gradient = vec![0.0; dimension];
+    let mut buffer = vec![0.0; dimension];
+    for (node, weight) in GAUSS_LEGENDRE_NODES.iter().zip(GAUSS_LEGENDRE_WEIGHTS) {
+        value += weight * half * f(middle + half * node, &mut buffer);
+        gradient
+            .iter_mut()
+            .zip(&buffer)
+            .for_each(|(g, b)| *g += weight * half * b);
+    }
+    (value, gradient)
+}
+
+/// Kronrod nodes on `[0, 1]`; the odd-indexed ones are the seven-point Gauss
+/// nodes.
+const KRONROD_NODES: [Float; 8] = [
+    0.991_455_371_120_812_6,
+    0.949_107_912_342_758_5,
+    0.864_864_423_359_769_1,
+    0.741_531_185_599_394_4,
+    0.586_087_235_467_691_1,
+    0.405_845_151_377_397_2,
+    0.207_784_955_007_898_5,
+    0.0,
+];
+const KRONROD_WEIGHTS: [Float; 8] = [
+    0.022_935_322_010_529_22,
+    0.063_092_092_629_978_55,
+    0.104_790_010_322_250_2,
+    0.140_653_259_715_525_9,
+    0.169_004_726_639_267_9,
+    0.190_350_578_064_785_4,
+    0.204_432_940_075_298_9,
+    0.209_482_141_084_727_8,
+];
+const GAUSS_WEIGHTS: [Float; 4] = [
+    0.129_484_966_168_869_7,
+    0.279_705_391_489_276_7,
+    0.381_830_050_505_118_9,
+    0.417_959_183_673_469_4,
+];
+
+/// Relative accuracy requested by [`integrate_box`].
+pub const TOLERANCE: Float = 1e-10;
+const MAX_INTERVALS: usize = 500;
+
+/// Change of variable mapping an integral with infinite limits onto a finite
+/// interval of `t`.
+#[derive(Debug, Clone, Copy)]
+enum Substitution {
+    Finite(Float, Float),
+    /// `x = low + t / (1 - t)` on `[0, 1)`.
+    Lower(Float),
+    /// `x = high - (1 - t) / t` on `(0, 1]`.
+    Upper(Float),
+    /// `x = t / (1 - t^2)` on `(-1, 1)`.
+    Both,
+}
+
+impl Substitution {
+    fn new(low: Float, high: Float) -> Self {
+        match (low.is_finite(), high.is_finite()) {
+            (true, true) => Substitution::Finite(low, high),
+            (true, false) => Substitution::Lower(low),
+            (false, true) => Substitution::Upper(high),
+            (false, false) => Substitution::Both,
+        }
+    }
+
+    fn interval(&self) -> (Float, Float) {
+        match *self {
+            Substitution::Finite(low, high) => (low, high),
+            Substitution::Lower(_) | Substitution::Upper(_) => (0.0, 1.0),
+            Substitution::Both => (-1.0, 1.0),
+        }
+    }
+
+    /// The value of `x` and the Jacobian `dx / dt`.
+    fn apply(&self, t: Float) -> (Float, Float) {
+        match *self {
+            Substitution::Finite(..) => (t, 1.0),
+            Substitution::Lower(low) => (low + t / (1.0 - t), 1.0 / ((1.0 - t) * (1.0 - t))),
+            Substitution::Upper(high) => (high - (1.0 - t) / t, 1.0 / (t * t)),
+            Substitution::Both => {
+                let s = 1.0 - t * t;
+                (t / s, (1.0 + t * t) / (s * s))
+            }
+        }
+    }
+}
+
+struct Interval {
+    low: Float,
+    high: Float,
+    value: Float,
+    gradient: Vec<Float>,
+    error: Float,
+}
+
+/// Applies the 7-point Gauss / 15-point Kronrod pair to `[low, high]`, taking
+/// the difference of the two rules as the error estimate.
+fn kronrod_interval(
+    low: Float,
+    high: Float,
+    dimension: usize,
+    f: &mut impl FnMut(Float, &mut [Float]) -> Float,
+) -> Interval {
+    let half = 0.5 * (high - low);
+    let middle = 0.5 * (high + low);
+    let mut kronrod = 0.0;
+    let mut gauss = 0.0;
+    let mut gradient = vec![0.0; dimension];
+    let mut buffer = vec![0.0; dimension];
+    let mut add = |x: Float, weight: Float, gauss_weight: Float, gauss: &mut Float| {
+        let value = f(x, &mut buffer);
+        kronrod += weight * value;
+        *gauss += gauss_weight * value;
+        gradient
+            .iter_mut()
+            .zip(&buffer)
+            .for_each(|(g, b)| *g += weight * half * b);
+    };
+    for (i, (node, weight)) in KRONROD_NODES.iter().zip(KRONROD_WEIGHTS).enumerate() {
+        let gauss_weight = if i % 2 == 1 {
+            GAUSS_WEIGHTS[i / 2]
+        } else {
+            0.0
+        };
+        if *node == 0.0 {
+            add(middle, weight, gauss_weight, &mut gauss);
+        } else {
+            add(middle - half * node, weight, gauss_weight, &mut gauss);
+            add(middle + half * node, weight, gauss_weight, &mut gauss);
+        }
+    }
+    Interval {
+        low,
+        high,
+        value: kronrod * half,
+        gradient,
+        error: ((kronrod - gauss) * half).abs(),
+    }
+}
+
+/// Integrates `f` and its gradient over `[low, high]`, either of which may be
+/// infinite, by adaptive Gauss-Kronrod quadrature. The interval with the
+/// largest error estimate is bisected until the total error is below
+/// `tolerance` relative to the integral.
+pub fn gauss_kronrod(
+    low: Float,
+    high: Float,
+    dimension: usize,
+    tolerance: Float,
+    mut f: impl FnMut(Float, &mut [Float]) -> Float,
+) -> (Float, Vec<Float>) {
+    let substitution = Substitution::new(low, high);
+    let (a, b) = substitution.interval();
+    let mut integrand = |t: Float, gradient: &mut [Float]| {
+        let (x, jacobian) = substitution.apply(t);
+        let value = f(x, gradient);
+        gradient.iter_mut().for_each(|g| *g *= jacobian);
+        value * jacobian
+    };
+
+    let mut intervals = vec![kronrod_interval(a, b, dimension, &mut integrand)];
+    while intervals.len() < MAX_INTERVALS {
+        let value: Float = intervals.iter().map(|i| i.value).sum();
+        let error: Float = intervals.iter().map(|i| i.error).sum();
+        if error <= tolerance * value.abs() || error < Float::MIN_POSITIVE {
+            break;
+        }
+        let worst = (0..intervals.len())
+            .max_by(|&i, &j| intervals[i].error.total_cmp(&intervals[j].error))
+            .unwrap();
+        let interval = intervals.swap_remove(worst);
+        let middle = 0.5 * (interval.low + interval.high);
+        if middle <= interval.low || middle >= interval.high {
+            intervals.push(interval);
+            break;
+        }
+        intervals.push(kronrod_interval(
+            interval.low,
+            middle,
+            dimension,
+            &mut integrand,
+        ));
+        intervals.push(kronrod_interval(
+            middle,
+            interval.high,
+            dimension,
+            &mut integrand,
+        ));
+    }
+
+    let mut value = 0.0;
+    let mut gradient = vec![0.0; dimension];
+    for interval in &intervals {
+        value += interval.value;
+        gradient
+            .iter_mut()
+            .zip(&interval.gradient)
+            .for_each(|(g, i)| *g += i);
+    }
+    (value, gradient)
+}
+
+/// Integrates `f` and its gradient over a box of `ranges`, one adaptive
+/// integration nested inside the other.
+pub fn integrate_box(
+    ranges: &[(Float, Float)],
+    dimension: usize,
+    tolerance: Float,
+    f: &mut dyn FnMut(&[Float], &mut [Float]) -> Float,
+) -> (Float, Vec<Float>) {
+    let mut point = vec![0.0; ranges.len()];
+    nested(ranges, 0, &mut point, dimension, tolerance, f)
+}
+
+/// Integral and gradient over a box of `ranges` from an antiderivative `f`
+/// in every coordinate, summing its values at the corners with alternating
+/// signs.
+pub fn antiderivative_box(
+    ranges: &[(Float, Float)],
+    dimension: usize,
+    f: &mut dyn FnMut(&[Float], &mut [Float]) -> Float,
+) -> (Float, Vec<Float>) {
+    let mut value = 0.0;
+    let mut gradient = vec![0.0; dimension];
+    let mut corner_gradient = vec![0.0; dimension];
+    let mut point = vec![0.0; ranges.len()];
+    for corner in 0..1usize << ranges.len() {
+        let mut sign = 1.0;
+        for (axis, &(low, high)) in ranges.iter().enumerate() {
+            if corner >> axis & 1 == 1 {
+                point[axis] = high;
+            } else {
+                point[axis] = low;
+                sign = -sign;
+            }
+        }
+        value += sign * f(&point, &mut corner_gradient);
+        gradient
+            .iter_mut()
+            .zip(&corner_gradient)
+            .for_each(|(g, c)| *g += sign * c);
+    }
+    (value, gradient)
+}
+
+/// Logarithm of an integral, writing its gradient into `gradient`.
+pub fn log_integral(
+    (integral, integral_gradient): (Float, Vec<Float>),
+    gradient: &mut [Float],
+) -> Float {
+    gradient
+        .iter_mut()
+        .zip(integral_gradient)
+        .for_each(|(g, d)| *g = d / integral);
+    integral.ln()
+}
+
+fn nested(
+    ranges: &[(Float, Float)],
+    axis: usize,
+    point: &mut [Float],
+    dimension: usize,
+    tolerance: Float,
+    f: &mut dyn FnMut(&[Float], &mut [Float]) -> Float,
+) -> (Float, Vec<Float>) {
+    let (low, high) = ranges[axis];
+    gauss_kronrod(low, high, dimension, tolerance, |x, gradient| {
+        point[axis] = x;
+        if axis + 1 == ranges.len() {
+            f(point, gradient)
+        } else {
+            let (value, inner) = nested(ranges, axis + 1, point, dimension, tolerance, f);
+            gradient.copy_from_slice(&inner);
+            value
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intermediate_representation::FloatConsts;
+
+    #[test]
+    fn gauss_kronrod_integrals() {
+        // Gaussian of width s: the integral is s sqrt(2 pi), its derivative
+        // with respect to s is sqrt(2 pi).
+        let s: Float = 0.3;
+        let gaussian = |x: Float, gradient: &mut [Float]| {
+            let value = (-0.5 * x * x / (s * s)).exp();
+            gradient[0] = value * x * x / (s * s * s);
+            value
+        };
+        let expected = (2.0 * Float::PI).sqrt();
+        for (low, high) in [
+            (-10.0, 10.0),
+            (Float::NEG_INFINITY, Float::INFINITY),
+            (0.0, Float::INFINITY),
+            (Float::NEG_INFINITY, 0.0),
+        ] {
+            let fraction = if low == 0.0 || high == 0.0 { 0.5 } else { 1.0 };
+            let (value, gradient) = gauss_kronrod(low, high, 1, 1e-12, gaussian);
+            assert!(
+                (value / (fraction * s * expected) - 1.0).abs() < 1e-10,
+                "{}",
+                value
+            );
+            assert!(
+                (gradient[0] / (fraction * expected) - 1.0).abs() < 1e-8,
+                "{:?}",
+                gradient
+            );
+        }
+
+        let (value, _) = gauss_kronrod(0.0, 1.0, 0, 1e-12, |x, _| x.sqrt());
+        assert!((value - 2.0 / 3.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn antiderivative_corners() {
+        // F = x^2 y^3 / 6 + a x y is the antiderivative of x y^2 + a.
+        let a = 0.7;
+        let ranges = [(0.5, 2.0), (-1.0, 1.5)];
+        let (value, gradient) = antiderivative_box(&ranges, 1, &mut |p, g| {
+            g[0] = p[0] * p[1];
+            p[0] * p[0] * p[1].powi(3) / 6.0 + a * p[0] * p[1]
+        });
+        let area = 1.5 * 2.5;
+        let expected = (4.0 - 0.25) / 2.0 * (3.375 + 1.0) / 3.0 + a * area;
+        assert!((value - expected).abs() < 1e-12);
+        assert!((gradient[0] - area).abs() < 1e-12);
+    }
+
+    #[test]
+    fn box_integral() {
+        let (value, gradient) = integrate_box(&[(0.0, 1.0), (0.0, 2.0)], 1, 1e-12, &mut |x, g| {
+            g[0] = x[0] * x[1];
+            3.0 * x[0] * x[1]
+        });
+        assert!((value - 3.0).abs() < 1e-12);
+        assert!((gradient[0] - 1.0).abs() < 1e-12);
+    }
+}
@@ -1,6 +1,18 @@
+pub mod binned;
+pub mod composite;
+pub mod constraint;
 pub mod data;
+pub mod distribution;
 pub mod generation;
+pub mod histogram;
+pub mod integration;
+pub mod likelihood;
+pub mod matrix;
+pub mod minimize;
+pub mod model;
 pub mod parameter;
+pub mod simultaneous;
+pub mod transform;
 
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
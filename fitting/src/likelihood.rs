@@ -0,0 +1,286 @@
+use intermediate_representation::Float;
+use rayon::prelude::*;
+
+use crate::data::Dataset;
+use crate::matrix::Matrix;
+use crate::minimize::{Hesse, Objective};
+use crate::model::Model;
+use crate::parameter::Parameter;
+
+/// How the covariance of a fit to weighted events is corrected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeightedErrors {
+    /// `C` is the sum over events of `w^2` times the outer product of the
+    /// per-event gradient, which is asymptotically correct.
+    Asymptotic,
+    /// `C` is the Hessian of the likelihood with squared weights.
+    SumOfSquaredWeights,
+}
+
+/// Unbinned negative log-likelihood of a dataset, summing the per-event
+/// values and gradients of a model's log-likelihood less its
+/// log-normalization. For extended models expecting `nu` events, the
+/// extended term `nu - N ln(nu)` is added, where `N`
+/// is the number of events. Weighted events scale their contributions and
+/// `N` becomes the sum of weights.
+pub struct NegativeLogLikelihood<'a, M: Model + ?Sized> {
+    model: &'a M,
+    data: &'a Dataset,
+    weighted_errors: WeightedErrors,
+}
+
+impl<'a, M: Model + ?Sized> NegativeLogLikelihood<'a, M> {
+    pub fn new(model: &'a M, data: &'a Dataset) -> Self {
+        assert_eq!(
+            data.columns(),
+            model.data_names(),
+            "dataset columns must match the model's data"
+        );
+        Self {
+            model,
+            data,
+            weighted_errors: WeightedErrors::Asymptotic,
+        }
+    }
+
+    pub fn with_weighted_errors(mut self, weighted_errors: WeightedErrors) -> Self {
+        self.weighted_errors = weighted_errors;
+        self
+    }
+
+    pub fn model(&self) -> &M {
+        self.model
+    }
+
+    pub fn data(&self) -> &Dataset {
+        self.data
+    }
+
+    /// Value and gradient of the extended term, with the expected number of
+    /// events and its gradient.
+    fn extended_term(
+        &self,
+        parameters: &[Float],
+        observed: Float,
+    ) -> Option<(Float, Vec<Float>, Float, Vec<Float>)> {
+        let mut expected_gradient = vec![0.0; parameters.len()];
+        let expected = self
+            .model
+            .expected_events(parameters, &mut expected_gradient)?;
+        let gradient = expected_gradient
+            .iter()
+            .map(|d| (1.0 - observed / expected) * d)
+            .collect();
+        Some((
+            expected - observed * expected.ln(),
+            gradient,
+            expected,
+            expected_gradient,
+        ))
+    }
+
+    /// The model's parameters, as a starting point for the minimizer.
+    pub fn parameters(&self) -> Vec<Parameter> {
+        self.model.parameters()
+    }
+
+    /// Negative log-likelihood and gradient with each event weighted by
+    /// `weight` applied to its dataset weight.
+    fn weighted_value_and_gradient(
+        &self,
+        parameters: &[Float],
+        weight: impl Fn(Float) -> Float + Sync,
+    ) -> (Float, Vec<Float>) {
+        let n = parameters.len();
+        let (value, gradient, observed) = self
+            .data
+            .par_weighted_events()
+            .fold(
+                || (0.0, vec![0.0; n], 0.0, vec![0.0; n]),
+                |(value, mut gradient, observed, mut buffer), (event, w)| {
+                    let w = weight(w);
+                    let event_value = self.model.log_likelihood(parameters, event, &mut buffer);
+                    gradient
+                        .iter_mut()
+                        .zip(&buffer)
+                        .for_each(|(g, b)| *g += w * b);
+                    (value + w * event_value, gradient, observed + w, buffer)
+                },
+            )
+            .map(|(value, gradient, observed, _)| (value, gradient, observed))
+            .reduce(
+                || (0.0, vec![0.0; n], 0.0),
+                |(value_a, mut gradient_a, observed_a), (value_b, gradient_b, observed_b)| {
+                    gradient_a
+                        .iter_mut()
+                        .zip(gradient_b)
+                        .for_each(|(a, b)| *a += b);
+                    (value_a + value_b, gradient_a, observed_a + observed_b)
+                },
+            );
+        let mut normalization_gradient = vec![0.0; n];
+        let normalization = self
+            .model
+            .log_normalization(parameters, &mut normalization_gradient);
+        let mut value = observed * normalization - value;
+        let mut gradient: Vec<Float> = gradient
+            .iter()
+            .zip(&normalization_gradient)
+            .map(|(g, d)| observed * d - g)
+            .collect();
+        if let Some((term, derivative, _, _)) = self.extended_term(parameters, observed) {
+            value += term;
+            gradient
+                .iter_mut()
+                .zip(derivative)
+                .for_each(|(g, d)| *g += d);
+        }
+        (value, gradient)
+    }
+
+    fn weighted_hessian(
+        &self,
+        parameters: &[Float],
+        weight: impl Fn(Float) -> Float + Sync,
+    ) -> Option<Matrix> {
+        let n = parameters.len();
+        let (sum, observed) = self
+            .data
+            .par_weighted_events()
+            .try_fold(
+                || (vec![0.0; n * n], 0.0, vec![0.0; n], vec![0.0; n * n]),
+                |(mut sum, observed, mut gradient, mut hessian), (event, w)| {
+                    let w = weight(w);
+                    self.model.log_likelihood_hessian(
+                        parameters,
+                        event,
+                        &mut gradient,
+                        &mut hessian,
+                    )?;
+                    sum.iter_mut().zip(&hessian).for_each(|(s, h)| *s += w * h);
+                    Some((sum, observed + w, gradient, hessian))
+                },
+            )
+            .map(|accumulated| accumulated.map(|(sum, observed, _, _)| (sum, observed)))
+            .try_reduce(
+                || (vec![0.0; n * n], 0.0),
+                |(mut a, observed_a), (b, observed_b)| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    Some((a, observed_a + observed_b))
+                },
+            )?;
+        let mut normalization_gradient = vec![0.0; n];
+        let mut normalization_hessian = vec![0.0; n * n];
+        self.model.log_normalization_hessian(
+            parameters,
+            &mut normalization_gradient,
+            &mut normalization_hessian,
+        );
+        let mut matrix = Matrix::zeros(n);
+        for i in 0..n {
+            for j in 0..n {
+                matrix[(i, j)] = observed * normalization_hessian[i * n + j] - sum[i * n + j];
+            }
+        }
+        if let Some((_, _, expected, expected_gradient)) = self.extended_term(parameters, observed)
+        {
+            let scale = observed / (expected * expected);
+            matrix.add_outer_product(scale, &expected_gradient, &expected_gradient);
+        }
+        Some(matrix)
+    }
+
+    /// Sum over events of `w^2 s s^T`, where `s` is the gradient of the
+    /// event's normalized log-likelihood including the `ln(nu)` of an
+    /// extended model.
+    fn score_covariance(&self, parameters: &[Float]) -> Matrix {
+        let n = parameters.len();
+        let mut normalization_gradient = vec![0.0; n];
+        self.model
+            .log_normalization(parameters, &mut normalization_gradient);
+        let mut expected_gradient = vec![0.0; n];
+        if let Some(expected) = self
+            .model
+            .expected_events(parameters, &mut expected_gradient)
+        {
+            normalization_gradient
+                .iter_mut()
+                .zip(&expected_gradient)
+                .for_each(|(d, e)| *d -= e / expected);
+        }
+        self.data
+            .par_weighted_events()
+            .fold(
+                || (Matrix::zeros(n), vec![0.0; n]),
+                |(mut covariance, mut score), (event, w)| {
+                    self.model.log_likelihood(parameters, event, &mut score);
+                    score
+                        .iter_mut()
+                        .zip(&normalization_gradient)
+                        .for_each(|(s, d)| *s -= d);
+                    covariance.add_outer_product(w * w, &score, &score);
+                    (covariance, score)
+                },
+            )
+            .map(|(covariance, _)| covariance)
+            .reduce(
+                || Matrix::zeros(n),
+                |mut a, b| {
+                    a.add(&b);
+                    a
+                },
+            )
+    }
+}
+
+impl<M: Model + ?Sized> Objective for NegativeLogLikelihood<'_, M> {
+    fn dimension(&self) -> usize {
+        self.model.parameter_names().len()
+    }
+
+    fn error_definition(&self) -> Float {
+        0.5
+    }
+
+    fn value_and_gradient(&self, parameters: &[Float]) -> (Float, Vec<Float>) {
+        self.weighted_value_and_gradient(parameters, |w| w)
+    }
+
+    fn hessian(&self, parameters: &[Float]) -> Option<Matrix> {
+        self.weighted_hessian(parameters, |w| w)
+    }
+
+    fn gradient_covariance(&self, parameters: &[Float]) -> Option<Matrix> {
+        if !self.data.is_weighted() {
+            return None;
+        }
+        Some(match self.weighted_errors {
+            WeightedErrors::Asymptotic => self.score_covariance(parameters),
+            WeightedErrors::SumOfSquaredWeights => {
+                Hesse::new().hessian(&SquaredWeights(self), parameters)
+            }
+        })
+    }
+}
+
+/// The likelihood with every weight squared, whose Hessian is the `C` of the
+/// sum-of-weights-squared correction.
+struct SquaredWeights<'a, 'b, M: Model + ?Sized>(&'a NegativeLogLikelihood<'b, M>);
+
+impl<M: Model + ?Sized> Objective for SquaredWeights<'_, '_, M> {
+    fn dimension(&self) -> usize {
+        self.0.dimension()
+    }
+
+    fn error_definition(&self) -> Float {
+        self.0.error_definition()
+    }
+
+    fn value_and_gradient(&self, parameters: &[Float]) -> (Float, Vec<Float>) {
+        self.0.weighted_value_and_gradient(parameters, |w| w * w)
+    }
+
+    fn hessian(&self, parameters: &[Float]) -> Option<Matrix> {
+        self.0.weighted_hessian(parameters, |w| w * w)
+    }
+}
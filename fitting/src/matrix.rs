@@ -0,0 +1,215 @@
+use std::ops::{Index, IndexMut};
+
+use intermediate_representation::Float;
+
+/// Dense square matrix stored in row-major order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    size: usize,
+    elements: Vec<Float>,
+}
+
+impl Matrix {
+    pub fn zeros(size: usize) -> Self {
+        Self {
+            size,
+            elements: vec![0.0; size * size],
+        }
+    }
+
+    pub fn identity(size: usize) -> Self {
+        let mut matrix = Self::zeros(size);
+        for i in 0..size {
+            matrix[(i, i)] = 1.0;
+        }
+        matrix
+    }
+
+    pub fn from_diagonal(diagonal: &[Float]) -> Self {
+        let mut matrix = Self::zeros(diagonal.len());
+        for (i, value) in diagonal.iter().enumerate() {
+            matrix[(i, i)] = *value;
+        }
+        matrix
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn diagonal(&self) -> Vec<Float> {
+        (0..self.size).map(|i| self[(i, i)]).collect()
+    }
+
+    pub fn multiply_vector(&self, vector: &[Float]) -> Vec<Float> {
+        (0..self.size)
+            .map(|i| dot(&self.elements[i * self.size..(i + 1) * self.size], vector))
+            .collect()
+    }
+
+    pub fn multiply(&self, other: &Matrix) -> Matrix {
+        let mut product = Matrix::zeros(self.size);
+        for i in 0..self.size {
+            for j in 0..self.size {
+                product[(i, j)] = (0..self.size).map(|k| self[(i, k)] * other[(k, j)]).sum();
+            }
+        }
+        product
+    }
+
+    pub fn quadratic_form(&self, vector: &[Float]) -> Float {
+        dot(vector, &self.multiply_vector(vector))
+    }
+
+    /// Adds `scale * left * right^T` to the matrix.
+    pub fn add_outer_product(&mut self, scale: Float, left: &[Float], right: &[Float]) {
+        for i in 0..self.size {
+            for j in 0..self.size {
+                self[(i, j)] += scale * left[i] * right[j];
+            }
+        }
+    }
+
+    pub fn add(&mut self, other: &Matrix) {
+        self.elements
+            .iter_mut()
+            .zip(&other.elements)
+            .for_each(|(e, o)| *e += o);
+    }
+
+    pub fn scale(&mut self, factor: Float) {
+        self.elements.iter_mut().for_each(|e| *e *= factor);
+    }
+
+    /// Inverts the matrix by Gauss-Jordan elimination with partial pivoting,
+    /// returning `None` if it is singular.
+    pub fn inverse(&self) -> Option<Matrix> {
+        let n = self.size;
+        let mut work = self.clone();
+        let mut inverse = Matrix::identity(n);
+        for column in 0..n {
+            let pivot = (column..n)
+                .max_by(|&a, &b| work[(a, column)].abs().total_cmp(&work[(b, column)].abs()))
+                .unwrap();
+            if work[(pivot, column)].abs() <= Float::EPSILON * work.max_abs() {
+                return None;
+            }
+            work.swap_rows(column, pivot);
+            inverse.swap_rows(column, pivot);
+
+            let scale = 1.0 / work[(column, column)];
+            for j in 0..n {
+                work[(column, j)] *= scale;
+                inverse[(column, j)] *= scale;
+            }
+            for row in 0..n {
+                if row == column {
+                    continue;
+                }
+                let factor = work[(row, column)];
+                if factor == 0.0 {
+                    continue;
+                }
+                for j in 0..n {
+                    work[(row, j)] -= factor * work[(column, j)];
+                    inverse[(row, j)] -= factor * inverse[(column, j)];
+                }
+            }
+        }
+        Some(inverse)
+    }
+
+    /// Returns the lower-triangular Cholesky factor, or `None` if the matrix
+    /// is not positive definite.
+    pub fn cholesky(&self) -> Option<Matrix> {
+        let n = self.size;
+        let mut lower = Matrix::zeros(n);
+        for i in 0..n {
+            for j in 0..=i {
+                let mut sum = self[(i, j)];
+                for k in 0..j {
+                    sum -= lower[(i, k)] * lower[(j, k)];
+                }
+                if i == j {
+                    if sum <= 0.0 || sum.is_nan() {
+                        return None;
+                    }
+                    lower[(i, i)] = sum.sqrt();
+                } else {
+                    lower[(i, j)] = sum / lower[(j, j)];
+                }
+            }
+        }
+        Some(lower)
+    }
+
+    pub fn is_positive_definite(&self) -> bool {
+        self.cholesky().is_some()
+    }
+
+    fn max_abs(&self) -> Float {
+        self.elements.iter().fold(0.0, |m, e| m.max(e.abs()))
+    }
+
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        for j in 0..self.size {
+            self.elements.swap(a * self.size + j, b * self.size + j);
+        }
+    }
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = Float;
+
+    fn index(&self, (row, column): (usize, usize)) -> &Float {
+        &self.elements[row * self.size + column]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (row, column): (usize, usize)) -> &mut Float {
+        &mut self.elements[row * self.size + column]
+    }
+}
+
+pub fn dot(left: &[Float], right: &[Float]) -> Float {
+    left.iter().zip(right).map(|(l, r)| l * r).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_of_symmetric_matrix() {
+        let mut matrix = Matrix::zeros(3);
+        let values = [[4.0, 1.0, 0.5], [1.0, 3.0, 0.2], [0.5, 0.2, 2.0]];
+        for i in 0..3 {
+            for j in 0..3 {
+                matrix[(i, j)] = values[i][j];
+            }
+        }
+        let inverse = matrix.inverse().unwrap();
+        for i in 0..3 {
+            let column: Vec<Float> = (0..3).map(|k| inverse[(k, i)]).collect();
+            let product = matrix.multiply_vector(&column);
+            for (j, value) in product.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        let mut matrix = Matrix::zeros(2);
+        matrix[(0, 0)] = 1.0;
+        matrix[(0, 1)] = 2.0;
+        matrix[(1, 0)] = 2.0;
+        matrix[(1, 1)] = 4.0;
+        assert!(matrix.inverse().is_none());
+    }
+}
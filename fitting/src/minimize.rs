@@ -0,0 +1,165 @@
+use intermediate_representation::Float;
+
+use crate::constraint::ConstraintPull;
+use crate::matrix::Matrix;
+
+pub mod hesse;
+pub mod migrad;
+pub mod minimizer;
+pub mod minos;
+
+pub use hesse::Hesse;
+pub use migrad::{Migrad, Update};
+pub use minimizer::Minimizer;
+pub use minos::{Minos, MinosError};
+
+/// A scalar function to be minimized, together with its gradient.
+pub trait Objective {
+    fn dimension(&self) -> usize;
+
+    /// Change in the objective corresponding to one standard deviation:
+    /// 0.5 for a negative log-likelihood, 1.0 for a chi-square.
+    fn error_definition(&self) -> Float;
+
+    fn value_and_gradient(&self, parameters: &[Float]) -> (Float, Vec<Float>);
+
+    fn value(&self, parameters: &[Float]) -> Float {
+        self.value_and_gradient(parameters).0
+    }
+
+    /// Analytic second derivatives, if the objective can provide them.
+    fn hessian(&self, _parameters: &[Float]) -> Option<Matrix> {
+        None
+    }
+
+    /// Covariance `C` of the gradient for objectives summed over weighted
+    /// events. When present, HESSE reports the sandwich `H^-1 C H^-1` instead
+    /// of the inverse Hessian.
+    fn gradient_covariance(&self, _parameters: &[Float]) -> Option<Matrix> {
+        None
+    }
+
+    /// Pulls of any external constraints included in the objective.
+    fn constraint_pulls(&self, _parameters: &[Float]) -> Vec<ConstraintPull> {
+        Vec::new()
+    }
+}
+
+/// View of an objective in which only the parameters at the `free` indices
+/// vary while the others are held at `values`.
+pub(crate) struct Restricted<'a, O: Objective + ?Sized> {
+    objective: &'a O,
+    values: Vec<Float>,
+    free: Vec<usize>,
+}
+
+impl<'a, O: Objective + ?Sized> Restricted<'a, O> {
+    pub(crate) fn new(objective: &'a O, values: Vec<Float>, free: Vec<usize>) -> Self {
+        Self {
+            objective,
+            values,
+            free,
+        }
+    }
+
+    pub(crate) fn restrict(&self, full: &[Float]) -> Vec<Float> {
+        self.free.iter().map(|&i| full[i]).collect()
+    }
+
+    fn submatrix(&self, full: &Matrix) -> Matrix {
+        let mut matrix = Matrix::zeros(self.free.len());
+        for (a, &i) in self.free.iter().enumerate() {
+            for (b, &j) in self.free.iter().enumerate() {
+                matrix[(a, b)] = full[(i, j)];
+            }
+        }
+        matrix
+    }
+
+    pub(crate) fn expand(&self, parameters: &[Float]) -> Vec<Float> {
+        let mut full = self.values.clone();
+        for (&i, value) in self.free.iter().zip(parameters) {
+            full[i] = *value;
+        }
+        full
+    }
+}
+
+impl<O: Objective + ?Sized> Objective for Restricted<'_, O> {
+    fn dimension(&self) -> usize {
+        self.free.len()
+    }
+
+    fn error_definition(&self) -> Float {
+        self.objective.error_definition()
+    }
+
+    fn value_and_gradient(&self, parameters: &[Float]) -> (Float, Vec<Float>) {
+        let (value, gradient) = self.objective.value_and_gradient(&self.expand(parameters));
+        (value, self.restrict(&gradient))
+    }
+
+    fn hessian(&self, parameters: &[Float]) -> Option<Matrix> {
+        let full = self.objective.hessian(&self.expand(parameters))?;
+        Some(self.submatrix(&full))
+    }
+
+    fn gradient_covariance(&self, parameters: &[Float]) -> Option<Matrix> {
+        let full = self
+            .objective
+            .gradient_covariance(&self.expand(parameters))?;
+        Some(self.submatrix(&full))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FitStatus {
+    Converged,
+    IterationLimitReached,
+    LineSearchFailed,
+}
+
+#[derive(Debug, Clone)]
+pub struct FitResult {
+    pub values: Vec<Float>,
+    pub minimum: Float,
+    pub edm: Float,
+    pub iterations: usize,
+    pub function_calls: usize,
+    pub status: FitStatus,
+    /// Indices of the parameters varied in the fit; the rows and columns of
+    /// `covariance` follow this order.
+    pub floating: Vec<usize>,
+    pub covariance: Option<Matrix>,
+    pub minos_errors: Vec<Option<MinosError>>,
+    pub pulls: Vec<ConstraintPull>,
+}
+
+impl FitResult {
+    pub fn is_valid(&self) -> bool {
+        self.status == FitStatus::Converged
+    }
+
+    /// Parabolic error of every parameter, zero for fixed ones.
+    pub fn errors(&self) -> Option<Vec<Float>> {
+        let covariance = self.covariance.as_ref()?;
+        let mut errors = vec![0.0; self.values.len()];
+        for (k, &i) in self.floating.iter().enumerate() {
+            errors[i] = covariance[(k, k)].sqrt();
+        }
+        Some(errors)
+    }
+
+    /// Correlation matrix of the floating parameters.
+    pub fn correlation(&self) -> Option<Matrix> {
+        let covariance = self.covariance.as_ref()?;
+        let errors: Vec<Float> = covariance.diagonal().iter().map(|v| v.sqrt()).collect();
+        let mut correlation = covariance.clone();
+        for i in 0..covariance.size() {
+            for j in 0..covariance.size() {
+                correlation[(i, j)] /= errors[i] * errors[j];
+            }
+        }
+        Some(correlation)
+    }
+}
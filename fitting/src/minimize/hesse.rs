@@ -0,0 +1,79 @@
+use intermediate_representation::Float;
+
+use crate::matrix::Matrix;
+use crate::minimize::{FitResult, Objective};
+
+/// Computes parameter uncertainties from the second derivatives of the
+/// objective at its minimum, in the spirit of MINUIT's HESSE.
+#[derive(Debug, Clone, Default)]
+pub struct Hesse {}
+
+impl Hesse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Second derivatives of `objective` at `values`, taken from the objective
+    /// when it provides them and otherwise from central differences of the
+    /// gradient.
+    pub fn hessian<O: Objective + ?Sized>(&self, objective: &O, values: &[Float]) -> Matrix {
+        if let Some(hessian) = objective.hessian(values) {
+            return hessian;
+        }
+
+        let n = values.len();
+        let mut hessian = Matrix::zeros(n);
+        for i in 0..n {
+            let step = Float::EPSILON.cbrt() * values[i].abs().max(1.0);
+            let mut forward = values.to_vec();
+            forward[i] += step;
+            let mut backward = values.to_vec();
+            backward[i] -= step;
+            let (_, gradient_forward) = objective.value_and_gradient(&forward);
+            let (_, gradient_backward) = objective.value_and_gradient(&backward);
+            for j in 0..n {
+                hessian[(i, j)] = (gradient_forward[j] - gradient_backward[j]) / (2.0 * step);
+            }
+        }
+        for i in 0..n {
+            for j in 0..i {
+                let mean = 0.5 * (hessian[(i, j)] + hessian[(j, i)]);
+                hessian[(i, j)] = mean;
+                hessian[(j, i)] = mean;
+            }
+        }
+        hessian
+    }
+
+    /// Covariance matrix `2 * up * H^-1`, or `None` if the Hessian is not
+    /// positive definite. Objectives providing a gradient covariance `C` get
+    /// the sandwich `H^-1 C H^-1` instead.
+    pub fn covariance<O: Objective + ?Sized>(
+        &self,
+        objective: &O,
+        values: &[Float],
+    ) -> Option<Matrix> {
+        let hessian = self.hessian(objective, values);
+        if !hessian.is_positive_definite() {
+            return None;
+        }
+        let mut covariance = hessian.inverse()?;
+        if let Some(gradient_covariance) = objective.gradient_covariance(values) {
+            return Some(
+                covariance
+                    .multiply(&gradient_covariance)
+                    .multiply(&covariance),
+            );
+        }
+        covariance.scale(2.0 * objective.error_definition());
+        Some(covariance)
+    }
+
+    /// Attaches the covariance matrix at the fitted values to `result`,
+    /// returning whether it could be computed.
+    pub fn run<O: Objective + ?Sized>(&self, objective: &O, result: &mut FitResult) -> bool {
+        result.covariance = self.covariance(objective, &result.values);
+        result.floating = (0..result.values.len()).collect();
+        result.covariance.is_some()
+    }
+}
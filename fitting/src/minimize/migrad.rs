@@ -0,0 +1,353 @@
+use std::cell::Cell;
+
+use intermediate_representation::Float;
+
+use crate::matrix::{Matrix, dot};
+use crate::minimize::{FitResult, FitStatus, Objective};
+
+const MAX_LINE_SEARCH_STEPS: usize = 30;
+const SUFFICIENT_DECREASE: Float = 1e-4;
+const CURVATURE: Float = 0.9;
+
+/// Formula used to update the inverse Hessian approximation after each step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    Bfgs,
+    Dfp,
+}
+
+/// Variable-metric minimizer in the spirit of MINUIT's MIGRAD.
+#[derive(Debug, Clone)]
+pub struct Migrad {
+    pub tolerance: Float,
+    pub max_iterations: usize,
+    pub update: Update,
+}
+
+impl Default for Migrad {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.1,
+            max_iterations: 1000,
+            update: Update::Bfgs,
+        }
+    }
+}
+
+struct Counted<'a, O: Objective + ?Sized> {
+    objective: &'a O,
+    calls: Cell<usize>,
+}
+
+impl<O: Objective + ?Sized> Counted<'_, O> {
+    fn evaluate(&self, parameters: &[Float]) -> (Float, Vec<Float>) {
+        self.calls.set(self.calls.get() + 1);
+        self.objective.value_and_gradient(parameters)
+    }
+}
+
+impl Migrad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Float) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn with_update(mut self, update: Update) -> Self {
+        self.update = update;
+        self
+    }
+
+    pub fn minimize<O: Objective + ?Sized>(&self, objective: &O, initial: &[Float]) -> FitResult {
+        let steps: Vec<Float> = initial.iter().map(|x| 0.1 * x.abs().max(1.0)).collect();
+        self.minimize_with_steps(objective, initial, &steps)
+    }
+
+    /// Minimizes starting from `initial`, using `steps` as the expected scale
+    /// of each parameter when seeding the inverse Hessian.
+    pub fn minimize_with_steps<O: Objective + ?Sized>(
+        &self,
+        objective: &O,
+        initial: &[Float],
+        steps: &[Float],
+    ) -> FitResult {
+        assert_eq!(initial.len(), objective.dimension());
+        assert_eq!(steps.len(), objective.dimension());
+
+        let counted = Counted {
+            objective,
+            calls: Cell::new(0),
+        };
+        let edm_target = 0.002 * self.tolerance * objective.error_definition();
+
+        let mut x = initial.to_vec();
+        let (mut f, mut g) = counted.evaluate(&x);
+        let mut v = self.seed(&counted, &x, &g, steps);
+        let mut edm = 0.5 * v.quadratic_form(&g);
+        let mut just_seeded = true;
+
+        let mut status = FitStatus::IterationLimitReached;
+        let mut iterations = 0;
+        while iterations < self.max_iterations {
+            if edm.is_finite() && edm >= 0.0 && edm < edm_target {
+                if just_seeded {
+                    status = FitStatus::Converged;
+                    break;
+                }
+                // Confirm convergence with a fresh curvature estimate, since
+                // the updated metric can underestimate the distance to the
+                // minimum.
+                v = self.seed(&counted, &x, &g, steps);
+                edm = 0.5 * v.quadratic_form(&g);
+                just_seeded = true;
+                continue;
+            }
+            iterations += 1;
+
+            let mut direction: Vec<Float> = v.multiply_vector(&g).iter().map(|d| -d).collect();
+            let mut slope = dot(&g, &direction);
+            if slope >= 0.0 || slope.is_nan() {
+                v = self.seed(&counted, &x, &g, steps);
+                just_seeded = true;
+                direction = v.multiply_vector(&g).iter().map(|d| -d).collect();
+                slope = dot(&g, &direction);
+            }
+
+            let Some((x_new, f_new, g_new)) = self.line_search(&counted, &x, f, &direction, slope)
+            else {
+                if just_seeded {
+                    status = FitStatus::LineSearchFailed;
+                    break;
+                }
+                v = self.seed(&counted, &x, &g, steps);
+                edm = 0.5 * v.quadratic_form(&g);
+                just_seeded = true;
+                continue;
+            };
+
+            let dx: Vec<Float> = x_new.iter().zip(&x).map(|(a, b)| a - b).collect();
+            let dg: Vec<Float> = g_new.iter().zip(&g).map(|(a, b)| a - b).collect();
+            self.update_inverse_hessian(&mut v, &dx, &dg);
+            just_seeded = false;
+
+            x = x_new;
+            f = f_new;
+            g = g_new;
+            edm = 0.5 * v.quadratic_form(&g);
+            if edm < 0.0 {
+                v = self.seed(&counted, &x, &g, steps);
+                edm = 0.5 * v.quadratic_form(&g);
+                just_seeded = true;
+            }
+        }
+
+        let dimension = x.len();
+        let pulls = objective.constraint_pulls(&x);
+        FitResult {
+            values: x,
+            minimum: f,
+            edm,
+            iterations,
+            function_calls: counted.calls.get(),
+            status,
+            floating: (0..dimension).collect(),
+            covariance: None,
+            minos_errors: vec![None; dimension],
+            pulls,
+        }
+    }
+
+    /// Builds the starting inverse Hessian from finite differences of the
+    /// gradient, falling back to its diagonal (or to the step sizes) when the
+    /// estimate is not positive definite.
+    fn seed<O: Objective + ?Sized>(
+        &self,
+        counted: &Counted<O>,
+        x: &[Float],
+        gradient: &[Float],
+        steps: &[Float],
+    ) -> Matrix {
+        let n = x.len();
+        let up = counted.objective.error_definition();
+        let mut hessian = Matrix::zeros(n);
+        for i in 0..n {
+            let difference = Float::EPSILON.sqrt() * x[i].abs().max(steps[i].abs()).max(1.0);
+            let mut shifted = x.to_vec();
+            shifted[i] += difference;
+            let (_, shifted_gradient) = counted.evaluate(&shifted);
+            for j in 0..n {
+                hessian[(i, j)] = (shifted_gradient[j] - gradient[j]) / difference;
+            }
+        }
+        for i in 0..n {
+            for j in 0..i {
+                let mean = 0.5 * (hessian[(i, j)] + hessian[(j, i)]);
+                hessian[(i, j)] = mean;
+                hessian[(j, i)] = mean;
+            }
+        }
+
+        if hessian.is_positive_definite()
+            && let Some(inverse) = hessian.inverse()
+        {
+            return inverse;
+        }
+        let diagonal: Vec<Float> = (0..n)
+            .map(|i| {
+                let curvature = hessian[(i, i)];
+                if curvature.is_finite() && curvature > 0.0 {
+                    1.0 / curvature
+                } else {
+                    steps[i] * steps[i] / (2.0 * up)
+                }
+            })
+            .collect();
+        Matrix::from_diagonal(&diagonal)
+    }
+
+    /// Line search along `direction` satisfying the strong Wolfe conditions,
+    /// bracketing the step first and then refining it by safeguarded
+    /// quadratic interpolation.
+    fn line_search<O: Objective + ?Sized>(
+        &self,
+        counted: &Counted<O>,
+        x: &[Float],
+        f: Float,
+        direction: &[Float],
+        slope: Float,
+    ) -> Option<(Vec<Float>, Float, Vec<Float>)> {
+        let evaluate = |alpha: Float| {
+            let point: Vec<Float> = x
+                .iter()
+                .zip(direction)
+                .map(|(x, d)| x + alpha * d)
+                .collect();
+            let (value, gradient) = counted.evaluate(&point);
+            let point_slope = dot(&gradient, direction);
+            (point, value, gradient, point_slope)
+        };
+        let sufficient = |alpha: Float, value: Float| {
+            value.is_finite() && value <= f + SUFFICIENT_DECREASE * alpha * slope
+        };
+
+        let mut low = (0.0, f, slope);
+        let mut high = None;
+        let mut alpha = 1.0;
+        for _ in 0..MAX_LINE_SEARCH_STEPS {
+            let (point, value, gradient, point_slope) = evaluate(alpha);
+            if !sufficient(alpha, value) || value >= low.1 {
+                high = Some((alpha, value, point_slope));
+            } else if point_slope.abs() <= -CURVATURE * slope {
+                return Some((point, value, gradient));
+            } else if point_slope * (alpha - low.0) >= 0.0 {
+                high = Some(low);
+                low = (alpha, value, point_slope);
+            } else {
+                low = (alpha, value, point_slope);
+            }
+
+            alpha = match high {
+                None => 2.0 * alpha,
+                Some(high) => {
+                    let (a, fa, da) = low;
+                    let (b, fb, _) = high;
+                    let curvature = fb - fa - da * (b - a);
+                    let trial = if fb.is_finite() && curvature > 0.0 {
+                        a - da * (b - a) * (b - a) / (2.0 * curvature)
+                    } else {
+                        0.5 * (a + b)
+                    };
+                    let (lower, upper) = if a < b { (a, b) } else { (b, a) };
+                    let margin = 0.1 * (upper - lower);
+                    trial.clamp(lower + margin, upper - margin)
+                }
+            };
+        }
+
+        if low.0 > 0.0 {
+            let (point, value, gradient, _) = evaluate(low.0);
+            return Some((point, value, gradient));
+        }
+        None
+    }
+
+    fn update_inverse_hessian(&self, v: &mut Matrix, dx: &[Float], dg: &[Float]) {
+        let delgam = dot(dx, dg);
+        if delgam <= 0.0 || delgam.is_nan() {
+            return;
+        }
+        let vg = v.multiply_vector(dg);
+        let gvg = dot(dg, &vg);
+        match &self.update {
+            Update::Dfp => {
+                v.add_outer_product(1.0 / delgam, dx, dx);
+                if gvg > 0.0 {
+                    v.add_outer_product(-1.0 / gvg, &vg, &vg);
+                }
+            }
+            Update::Bfgs => {
+                v.add_outer_product((1.0 + gvg / delgam) / delgam, dx, dx);
+                v.add_outer_product(-1.0 / delgam, dx, &vg);
+                v.add_outer_product(-1.0 / delgam, &vg, dx);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Rosenbrock;
+
+    impl Objective for Rosenbrock {
+        fn dimension(&self) -> usize {
+            2
+        }
+
+        fn error_definition(&self) -> Float {
+            1.0
+        }
+
+        fn value_and_gradient(&self, p: &[Float]) -> (Float, Vec<Float>) {
+            let (x, y) = (p[0], p[1]);
+            let value = (1.0 - x).powi(2) + 100.0 * (y - x * x).powi(2);
+            let gradient = vec![
+                -2.0 * (1.0 - x) - 400.0 * x * (y - x * x),
+                200.0 * (y - x * x),
+            ];
+            (value, gradient)
+        }
+    }
+
+    #[test]
+    fn minimizes_rosenbrock() {
+        for update in [Update::Bfgs, Update::Dfp] {
+            let result = Migrad::new()
+                .with_update(update)
+                .with_tolerance(1e-4)
+                .minimize(&Rosenbrock, &[-1.2, 1.0]);
+            assert_eq!(result.status, FitStatus::Converged, "{:?}", update);
+            assert!(
+                (result.values[0] - 1.0).abs() < 1e-3,
+                "{:?} {:?}",
+                update,
+                result
+            );
+            assert!(
+                (result.values[1] - 1.0).abs() < 1e-3,
+                "{:?} {:?}",
+                update,
+                result
+            );
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use intermediate_representation::Float;
+
+use crate::minimize::{FitResult, Hesse, Migrad, Minos, MinosError, Objective, Restricted};
+use crate::parameter::Parameter;
+use crate::transform::{Bounded, Transform};
+
+/// Runs MIGRAD, HESSE and MINOS on an objective described by a set of
+/// [`Parameter`]s, starting from their values and step sizes, keeping each one
+/// within its limits and holding fixed parameters at their values. Results
+/// are reported in external coordinates.
+pub struct Minimizer<'a, O: Objective + ?Sized> {
+    objective: &'a O,
+    parameters: Vec<Parameter>,
+    pub migrad: Migrad,
+    pub hesse: Hesse,
+    pub minos: Minos,
+}
+
+impl<'a, O: Objective + ?Sized> Minimizer<'a, O> {
+    pub fn new(objective: &'a O, parameters: Vec<Parameter>) -> Self {
+        assert_eq!(
+            objective.dimension(),
+            parameters.len(),
+            "one parameter is required per objective dimension"
+        );
+        Self {
+            objective,
+            parameters,
+            migrad: Migrad::new(),
+            hesse: Hesse::new(),
+            minos: Minos::new(),
+        }
+    }
+
+    pub fn with_migrad(mut self, migrad: Migrad) -> Self {
+        self.migrad = migrad;
+        self
+    }
+
+    pub fn with_minos(mut self, minos: Minos) -> Self {
+        self.minos = minos;
+        self
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut Parameter> {
+        self.parameters.iter_mut().find(|p| p.name() == name)
+    }
+
+    fn named(&mut self, name: &str) -> &mut Parameter {
+        self.parameter_mut(name)
+            .unwrap_or_else(|| panic!("unknown parameter `{}`", name))
+    }
+
+    /// Holds the named parameter at its current value in subsequent fits.
+    pub fn fix(&mut self, name: &str) {
+        self.named(name).fix();
+    }
+
+    pub fn release(&mut self, name: &str) {
+        self.named(name).release();
+    }
+
+    pub fn set_value(&mut self, name: &str, value: Float) {
+        self.named(name).set_value(value);
+    }
+
+    /// Takes the fitted values and errors as the starting point of the next
+    /// fit.
+    pub fn update_parameters(&mut self, result: &FitResult) {
+        let errors = result.errors();
+        for (i, parameter) in self.parameters.iter_mut().enumerate() {
+            parameter.set_value(result.values[i]);
+            if let Some(error) = errors.as_ref().map(|e| e[i]).filter(|e| *e > 0.0) {
+                parameter.set_error(error);
+            }
+        }
+    }
+
+    pub fn floating(&self) -> Vec<usize> {
+        (0..self.parameters.len())
+            .filter(|&i| !self.parameters[i].is_fixed())
+            .collect()
+    }
+
+    /// Calls `run` with the objective restricted to the floating parameters,
+    /// the others held at `values`, and expressed in internal coordinates.
+    fn internal<R>(
+        &self,
+        values: &[Float],
+        run: impl FnOnce(&Bounded<'_, Restricted<'_, O>>, &[usize]) -> R,
+    ) -> R {
+        let floating = self.floating();
+        let restricted = Restricted::new(self.objective, values.to_vec(), floating.clone());
+        let transforms = floating
+            .iter()
+            .map(|&i| Transform::for_parameter(&self.parameters[i]))
+            .collect();
+        let bounded = Bounded::new(&restricted, transforms);
+        run(&bounded, &floating)
+    }
+
+    pub fn migrad(&self) -> FitResult {
+        let values: Vec<Float> = self.parameters.iter().map(|p| p.value()).collect();
+        self.internal(&values, |bounded, floating| {
+            let external: Vec<Float> = floating.iter().map(|&i| values[i]).collect();
+            let errors: Vec<Float> = floating
+                .iter()
+                .map(|&i| self.parameters[i].error())
+                .collect();
+            let internal = bounded.to_internal(&external);
+            let steps = bounded.internal_steps(&internal, &errors);
+
+            let mut result = self.migrad.minimize_with_steps(bounded, &internal, &steps);
+            let mut full = values.clone();
+            for (&i, value) in floating.iter().zip(bounded.to_external(&result.values)) {
+                full[i] = value;
+            }
+            result.pulls = self.objective.constraint_pulls(&full);
+            result.values = full;
+            result.floating = floating.to_vec();
+            result.minos_errors = vec![None; values.len()];
+            result
+        })
+    }
+
+    /// Computes the covariance of the floating parameters in internal
+    /// coordinates and propagates it to the external ones.
+    pub fn hesse(&self, result: &mut FitResult) -> bool {
+        let covariance = self.internal(&result.values, |bounded, floating| {
+            let external: Vec<Float> = floating.iter().map(|&i| result.values[i]).collect();
+            let internal = bounded.to_internal(&external);
+            self.hesse
+                .covariance(bounded, &internal)
+                .map(|covariance| bounded.external_covariance(&internal, &covariance))
+        });
+        result.covariance = covariance;
+        result.floating = self.floating();
+        result.covariance.is_some()
+    }
+
+    /// Runs MIGRAD followed by HESSE.
+    pub fn fit(&self) -> FitResult {
+        let mut result = self.migrad();
+        self.hesse(&mut result);
+        result
+    }
+
+    /// Finds the crossings in internal coordinates and maps them back through
+    /// each parameter's transformation. Fixed parameters are skipped.
+    pub fn minos(&self, result: &mut FitResult, parameters: &[usize]) {
+        let errors = self.internal(&result.values, |bounded, floating| {
+            let external: Vec<Float> = floating.iter().map(|&i| result.values[i]).collect();
+            let internal = FitResult {
+                values: bounded.to_internal(&external),
+                floating: (0..floating.len()).collect(),
+                covariance: None,
+                minos_errors: vec![None; floating.len()],
+                ..result.clone()
+            };
+            parameters
+                .iter()
+                .filter_map(|&parameter| {
+                    let k = floating.iter().position(|&i| i == parameter)?;
+                    let error = self.minos.errors(bounded, &internal, k);
+                    let transform = bounded.transforms()[k];
+                    let best = internal.values[k];
+                    let value = result.values[parameter];
+                    // A decreasing transformation swaps the crossings, and one
+                    // that folds back at a limit can put both on one side.
+                    let lower = (transform.to_external(best + error.lower), error.lower_valid);
+                    let upper = (transform.to_external(best + error.upper), error.upper_valid);
+                    let (lower, upper) = if lower.0 <= upper.0 {
+                        (lower, upper)
+                    } else {
+                        (upper, lower)
+                    };
+                    let error = MinosError {
+                        lower: lower.0 - value,
+                        upper: upper.0 - value,
+                        lower_valid: lower.1,
+                        upper_valid: upper.1,
+                    };
+                    Some((parameter, error))
+                })
+                .collect::<Vec<_>>()
+        });
+        for (parameter, error) in errors {
+            result.minos_errors[parameter] = Some(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Parabola;
+
+    impl Objective for Parabola {
+        fn dimension(&self) -> usize {
+            2
+        }
+
+        fn error_definition(&self) -> Float {
+            0.5
+        }
+
+        fn value_and_gradient(&self, p: &[Float]) -> (Float, Vec<Float>) {
+            let value = 0.5 * (p[0] - 2.0).powi(2) + 0.5 * ((p[1] - 1.0) / 0.1).powi(2);
+            (value, vec![p[0] - 2.0, (p[1] - 1.0) / 0.01])
+        }
+    }
+
+    #[test]
+    fn limits_are_honored() {
+        let parameters = vec![
+            Parameter::new("a".to_string(), false)
+                .with_value(0.5)
+                .with_limits(0.0, 1.0),
+            Parameter::new("b".to_string(), false)
+                .with_value(3.0)
+                .with_lower_limit(0.0),
+        ];
+        let minimizer = Minimizer::new(&Parabola, parameters);
+        let mut result = minimizer.fit();
+
+        assert!(result.values[0] <= 1.0 && (result.values[0] - 1.0).abs() < 1e-2);
+        assert!((result.values[1] - 1.0).abs() < 1e-4);
+        let errors = result.errors().unwrap();
+        assert!((errors[1] - 0.1).abs() < 1e-3, "{:?}", errors);
+
+        minimizer.minos(&mut result, &[1]);
+        let error = result.minos_errors[1].unwrap();
+        assert!((error.upper - 0.1).abs() < 1e-3, "{:?}", error);
+        assert!((error.lower + 0.1).abs() < 1e-3, "{:?}", error);
+    }
+
+    #[test]
+    fn minos_with_upper_limit() {
+        let parameters = vec![
+            Parameter::new("a".to_string(), false).with_value(1.0),
+            Parameter::new("b".to_string(), false)
+                .with_value(0.5)
+                .with_upper_limit(3.0),
+        ];
+        let minimizer = Minimizer::new(&Parabola, parameters);
+        let mut result = minimizer.fit();
+        assert!((result.values[1] - 1.0).abs() < 1e-3, "{:?}", result);
+
+        minimizer.minos(&mut result, &[1]);
+        let error = result.minos_errors[1].unwrap();
+        assert!(error.is_valid(), "{:?}", error);
+        assert!((error.upper - 0.1).abs() < 1e-3, "{:?}", error);
+        assert!((error.lower + 0.1).abs() < 1e-3, "{:?}", error);
+    }
+
+    #[test]
+    fn fixed_parameters_are_not_varied() {
+        let parameters = vec![
+            Parameter::new("a".to_string(), false).with_value(0.5),
+            Parameter::new("b".to_string(), false).with_value(3.0),
+        ];
+        let mut minimizer = Minimizer::new(&Parabola, parameters);
+        minimizer.fix("a");
+        let result = minimizer.fit();
+
+        assert_eq!(result.values[0], 0.5);
+        assert!((result.values[1] - 1.0).abs() < 1e-4);
+        assert_eq!(result.floating, vec![1]);
+        assert_eq!(result.covariance.as_ref().unwrap().size(), 1);
+        assert_eq!(result.errors().unwrap()[0], 0.0);
+
+        minimizer.update_parameters(&result);
+        minimizer.release("a");
+        let result = minimizer.fit();
+        assert!((result.values[0] - 2.0).abs() < 1e-4);
+        assert_eq!(result.covariance.unwrap().size(), 2);
+    }
+}
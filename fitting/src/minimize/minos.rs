@@ -0,0 +1,273 @@
+use intermediate_representation::Float;
+
+use crate::minimize::{FitResult, Hesse, Migrad, Objective, Restricted};
+
+/// Asymmetric uncertainties of one parameter: signed offsets from the best-fit
+/// value to the points where the profiled objective rises by the error level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinosError {
+    pub lower: Float,
+    pub upper: Float,
+    pub lower_valid: bool,
+    pub upper_valid: bool,
+}
+
+impl MinosError {
+    pub fn is_valid(&self) -> bool {
+        self.lower_valid && self.upper_valid
+    }
+}
+
+/// Profile-likelihood errors in the spirit of MINUIT's MINOS. Each crossing is
+/// found by Newton iterations on the profiled objective, whose slope with
+/// respect to the scanned parameter is the objective's own gradient component
+/// at the profiled point.
+#[derive(Debug, Clone)]
+pub struct Minos {
+    pub migrad: Migrad,
+    pub level: Option<Float>,
+    pub tolerance: Float,
+    pub max_iterations: usize,
+}
+
+impl Default for Minos {
+    fn default() -> Self {
+        Self {
+            migrad: Migrad::new().with_tolerance(0.01),
+            level: None,
+            tolerance: 1e-3,
+            max_iterations: 30,
+        }
+    }
+}
+
+struct Crossing {
+    best: Float,
+    minimum: Float,
+    level: Float,
+    steps: Vec<Float>,
+}
+
+impl Minos {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rise of the objective above its minimum defining the interval; the
+    /// objective's error definition is used when unset.
+    pub fn with_level(mut self, level: Float) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    pub fn with_migrad(mut self, migrad: Migrad) -> Self {
+        self.migrad = migrad;
+        self
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Float) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn errors<O: Objective + ?Sized>(
+        &self,
+        objective: &O,
+        result: &FitResult,
+        parameter: usize,
+    ) -> MinosError {
+        let level = self.level.unwrap_or(objective.error_definition());
+        let steps = match result.errors() {
+            Some(errors) => errors,
+            None => match Hesse::new().covariance(objective, &result.values) {
+                Some(covariance) => covariance.diagonal().iter().map(|v| v.sqrt()).collect(),
+                None => result
+                    .values
+                    .iter()
+                    .map(|x| 0.1 * x.abs().max(1.0))
+                    .collect(),
+            },
+        };
+        let crossing = Crossing {
+            best: result.values[parameter],
+            minimum: result.minimum,
+            level,
+            steps,
+        };
+
+        let (upper, upper_valid) = self.crossing(objective, result, parameter, &crossing, 1.0);
+        let (lower, lower_valid) = self.crossing(objective, result, parameter, &crossing, -1.0);
+        MinosError {
+            lower,
+            upper,
+            lower_valid,
+            upper_valid,
+        }
+    }
+
+    /// Computes the errors of each of `parameters` and attaches them to
+    /// `result`.
+    pub fn run<O: Objective + ?Sized>(
+        &self,
+        objective: &O,
+        result: &mut FitResult,
+        parameters: &[usize],
+    ) {
+        for &parameter in parameters {
+            result.minos_errors[parameter] = Some(self.errors(objective, result, parameter));
+        }
+    }
+
+    /// Minimizes the objective with `parameter` fixed at `value`, returning the
+    /// rise above the crossing level, its slope with respect to `value` and the
+    /// profiled point.
+    fn profile<O: Objective + ?Sized>(
+        &self,
+        objective: &O,
+        parameter: usize,
+        value: Float,
+        start: &[Float],
+        crossing: &Crossing,
+    ) -> (Float, Float, Vec<Float>) {
+        let mut values = start.to_vec();
+        values[parameter] = value;
+        let free: Vec<usize> = (0..values.len()).filter(|&i| i != parameter).collect();
+        let restricted = Restricted::new(objective, values, free);
+
+        let initial = restricted.restrict(start);
+        let steps = restricted.restrict(&crossing.steps);
+        let profiled = self
+            .migrad
+            .minimize_with_steps(&restricted, &initial, &steps);
+        let point = restricted.expand(&profiled.values);
+        let (value, gradient) = objective.value_and_gradient(&point);
+        (
+            value - crossing.minimum - crossing.level,
+            gradient[parameter],
+            point,
+        )
+    }
+
+    /// Searches for the crossing on one side of the minimum, keeping a bracket
+    /// of distances below and above the level and falling back to secant or
+    /// bisection steps when the Newton step leaves it.
+    fn crossing<O: Objective + ?Sized>(
+        &self,
+        objective: &O,
+        result: &FitResult,
+        parameter: usize,
+        crossing: &Crossing,
+        direction: Float,
+    ) -> (Float, bool) {
+        let up = objective.error_definition();
+        let mut inside = (0.0, -crossing.level);
+        let mut outside: Option<(Float, Float)> = None;
+        let mut distance = crossing.steps[parameter] * (crossing.level / up).sqrt();
+        let mut start = result.values.clone();
+
+        for _ in 0..self.max_iterations {
+            let value = crossing.best + direction * distance;
+            let (delta, gradient, point) =
+                self.profile(objective, parameter, value, &start, crossing);
+            if delta.abs() < self.tolerance * crossing.level {
+                return (direction * distance, true);
+            }
+            if delta < 0.0 {
+                inside = (distance, delta);
+            } else {
+                outside = Some((distance, delta));
+            }
+            start = point;
+
+            let slope = direction * gradient;
+            let newton = if slope > 0.0 {
+                distance - delta / slope
+            } else {
+                Float::NAN
+            };
+            distance = match outside {
+                Some((outside_distance, outside_delta)) => {
+                    let (low, high) = (inside.0, outside_distance);
+                    let secant = inside.0
+                        - inside.1 * (outside_distance - inside.0) / (outside_delta - inside.1);
+                    if newton > low && newton < high {
+                        newton
+                    } else if secant > low && secant < high {
+                        secant
+                    } else {
+                        0.5 * (low + high)
+                    }
+                }
+                None => {
+                    if newton > distance {
+                        newton.min(4.0 * distance)
+                    } else {
+                        2.0 * distance
+                    }
+                }
+            };
+        }
+        (direction * distance, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Poisson counting likelihood for `lambda`, plus a nuisance parameter
+    /// correlated with it that profiles away exactly.
+    struct Counting {
+        observed: Float,
+    }
+
+    impl Objective for Counting {
+        fn dimension(&self) -> usize {
+            2
+        }
+
+        fn error_definition(&self) -> Float {
+            0.5
+        }
+
+        fn value_and_gradient(&self, p: &[Float]) -> (Float, Vec<Float>) {
+            let (lambda, nuisance) = (p[0], p[1]);
+            let pull = nuisance - 0.3 * lambda;
+            let value = lambda - self.observed * lambda.ln() + 0.5 * pull * pull;
+            let gradient = vec![1.0 - self.observed / lambda - 0.3 * pull, pull];
+            (value, gradient)
+        }
+    }
+
+    fn solve(f: impl Fn(Float) -> Float, mut low: Float, mut high: Float) -> Float {
+        for _ in 0..200 {
+            let middle = 0.5 * (low + high);
+            if (f(middle) > 0.0) == (f(high) > 0.0) {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+        0.5 * (low + high)
+    }
+
+    #[test]
+    fn poisson_interval() {
+        let objective = Counting { observed: 10.0 };
+        let mut result = Migrad::new().minimize(&objective, &[8.0, 1.0]);
+        Hesse::new().run(&objective, &mut result);
+        Minos::new().run(&objective, &mut result, &[0]);
+
+        let delta = |lambda: Float| {
+            lambda - 10.0 * lambda.ln() - (10.0 - 10.0 * (10.0 as Float).ln()) - 0.5
+        };
+        let upper = solve(delta, 10.0, 30.0) - 10.0;
+        let lower = solve(delta, 1.0, 10.0) - 10.0;
+
+        let error = result.minos_errors[0].unwrap();
+        assert!(error.is_valid());
+        assert!((error.upper - upper).abs() < 1e-2, "{:?} {}", error, upper);
+        assert!((error.lower - lower).abs() < 1e-2, "{:?} {}", error, lower);
+        assert!(result.minos_errors[1].is_none());
+    }
+}
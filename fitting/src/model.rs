@@ -0,0 +1,132 @@
+use intermediate_representation::Float;
+
+use crate::data::Data;
+use crate::parameter::Parameter;
+
+/// A probability model over named observables, implemented by
+/// `#[define_model]` for the user's struct. The `parameters` and `event`
+/// slices follow the order of [`Model::parameter_names`] and
+/// [`Model::data_names`], which is the declaration order of the struct
+/// fields.
+pub trait Model: Sync {
+    fn parameter_names(&self) -> Vec<String>;
+
+    fn data_names(&self) -> Vec<String>;
+
+    /// The model's own parameters, in layout order.
+    fn parameters(&self) -> Vec<Parameter>;
+
+    /// The model's observables, in layout order.
+    fn data(&self) -> Vec<Data>;
+
+    /// Index of the parameter holding the expected number of events, for
+    /// models declaring a `#[yield_parameter]`.
+    fn yield_index(&self) -> Option<usize> {
+        None
+    }
+
+    /// Expected number of events of an extended model, writing its gradient
+    /// into `gradient`, or `None` for models that only describe a shape. It
+    /// must be linear in the parameters; by default it is the yield
+    /// parameter.
+    fn expected_events(&self, parameters: &[Float], gradient: &mut [Float]) -> Option<Float> {
+        let index = self.yield_index()?;
+        gradient.fill(0.0);
+        gradient[index] = 1.0;
+        Some(parameters[index])
+    }
+
+    /// Value of the distribution for one event, writing its gradient with
+    /// respect to the parameters into `gradient`.
+    fn distribution(&self, parameters: &[Float], event: &[Float], gradient: &mut [Float]) -> Float;
+
+    /// Log-likelihood of one event, writing its gradient with respect to the
+    /// parameters into `gradient`. The normalization given by
+    /// [`Model::log_normalization`] is not included.
+    fn log_likelihood(
+        &self,
+        parameters: &[Float],
+        event: &[Float],
+        gradient: &mut [Float],
+    ) -> Float;
+
+    /// Log-likelihood of one event with its gradient and row-major Hessian,
+    /// or `None` if the model has no analytic second derivatives.
+    fn log_likelihood_hessian(
+        &self,
+        _parameters: &[Float],
+        _event: &[Float],
+        _gradient: &mut [Float],
+        _hessian: &mut [Float],
+    ) -> Option<Float> {
+        None
+    }
+
+    /// Logarithm of the integral of the distribution over the observables'
+    /// ranges, writing its gradient into `gradient`. Zero for models whose
+    /// distribution is normalized as written.
+    fn log_normalization(&self, _parameters: &[Float], gradient: &mut [Float]) -> Float {
+        gradient.fill(0.0);
+        0.0
+    }
+
+    /// [`Model::log_normalization`] with its row-major Hessian, by default
+    /// from central differences of the gradient.
+    fn log_normalization_hessian(
+        &self,
+        parameters: &[Float],
+        gradient: &mut [Float],
+        hessian: &mut [Float],
+    ) -> Float {
+        let n = parameters.len();
+        let value = self.log_normalization(parameters, gradient);
+        let mut point = parameters.to_vec();
+        let mut forward = vec![0.0; n];
+        let mut backward = vec![0.0; n];
+        for i in 0..n {
+            let step = Float::EPSILON.cbrt() * parameters[i].abs().max(1.0);
+            point[i] = parameters[i] + step;
+            self.log_normalization(&point, &mut forward);
+            point[i] = parameters[i] - step;
+            self.log_normalization(&point, &mut backward);
+            point[i] = parameters[i];
+            for j in 0..n {
+                hessian[i * n + j] = (forward[j] - backward[j]) / (2.0 * step);
+            }
+        }
+        for i in 0..n {
+            for j in 0..i {
+                let mean = 0.5 * (hessian[i * n + j] + hessian[j * n + i]);
+                hessian[i * n + j] = mean;
+                hessian[j * n + i] = mean;
+            }
+        }
+        value
+    }
+
+    fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.parameter_names().iter().position(|n| n == name)
+    }
+
+    fn data_index(&self, name: &str) -> Option<usize> {
+        self.data_names().iter().position(|n| n == name)
+    }
+
+    fn parameter_values(&self) -> Vec<Float> {
+        self.parameters().iter().map(|p| p.value()).collect()
+    }
+
+    /// Value of the distribution at the model's own parameter values.
+    fn evaluate(&self, event: &[Float]) -> Float {
+        let parameters = self.parameter_values();
+        let mut gradient = vec![0.0; parameters.len()];
+        self.distribution(&parameters, event, &mut gradient)
+    }
+
+    /// Log-likelihood at the model's own parameter values.
+    fn evaluate_log_likelihood(&self, event: &[Float]) -> Float {
+        let parameters = self.parameter_values();
+        let mut gradient = vec![0.0; parameters.len()];
+        self.log_likelihood(&parameters, event, &mut gradient)
+    }
+}
@@ -1,11 +1,98 @@
+use intermediate_representation::Float;
+
 #[derive(Debug, Clone)]
 pub struct Parameter {
     name: String,
     fixed: bool,
+    value: Float,
+    error: Float,
+    lower: Option<Float>,
+    upper: Option<Float>,
 }
 
 impl Parameter {
     pub fn new(name: String, fixed: bool) -> Self {
-        Self { name, fixed }
+        Self {
+            name,
+            fixed,
+            value: 0.0,
+            error: 0.1,
+            lower: None,
+            upper: None,
+        }
+    }
+
+    pub fn with_value(mut self, value: Float) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Expected uncertainty, used as the initial step size by the minimizers.
+    pub fn with_error(mut self, error: Float) -> Self {
+        self.error = error;
+        self
+    }
+
+    pub fn with_limits(mut self, lower: Float, upper: Float) -> Self {
+        assert!(lower < upper, "lower limit must be below upper limit");
+        self.lower = Some(lower);
+        self.upper = Some(upper);
+        self
+    }
+
+    pub fn with_lower_limit(mut self, lower: Float) -> Self {
+        if let Some(upper) = self.upper {
+            assert!(lower < upper, "lower limit must be below upper limit");
+        }
+        self.lower = Some(lower);
+        self
+    }
+
+    pub fn with_upper_limit(mut self, upper: Float) -> Self {
+        if let Some(lower) = self.lower {
+            assert!(lower < upper, "lower limit must be below upper limit");
+        }
+        self.upper = Some(upper);
+        self
+    }
+
+    pub fn fix(&mut self) {
+        self.fixed = true;
+    }
+
+    pub fn release(&mut self) {
+        self.fixed = false;
+    }
+
+    pub fn set_value(&mut self, value: Float) {
+        self.value = value;
+    }
+
+    pub fn set_error(&mut self, error: Float) {
+        self.error = error;
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.fixed
+    }
+
+    pub fn value(&self) -> Float {
+        self.value
+    }
+
+    pub fn error(&self) -> Float {
+        self.error
+    }
+
+    pub fn lower_limit(&self) -> Option<Float> {
+        self.lower
+    }
+
+    pub fn upper_limit(&self) -> Option<Float> {
+        self.upper
     }
 }
@@ -0,0 +1,153 @@
+use intermediate_representation::Float;
+
+use crate::data::Dataset;
+use crate::likelihood::NegativeLogLikelihood;
+use crate::matrix::Matrix;
+use crate::minimize::{Hesse, Objective};
+use crate::model::Model;
+use crate::parameter::Parameter;
+
+struct Category<'a> {
+    name: String,
+    likelihood: NegativeLogLikelihood<'a, dyn Model + 'a>,
+    /// Position in the global parameter vector of each of the model's
+    /// parameters.
+    indices: Vec<usize>,
+}
+
+impl Category<'_> {
+    fn local(&self, parameters: &[Float]) -> Vec<Float> {
+        self.indices.iter().map(|&i| parameters[i]).collect()
+    }
+
+    fn scatter(&self, local: &Matrix, global: &mut Matrix) {
+        for (a, &i) in self.indices.iter().enumerate() {
+            for (b, &j) in self.indices.iter().enumerate() {
+                global[(i, j)] += local[(a, b)];
+            }
+        }
+    }
+}
+
+/// Sum of the negative log-likelihoods of several models, each fitted to its
+/// own dataset. Parameters are identified across categories by the name of
+/// their [`Parameter`], so a parameter appearing in several models is shared
+/// and the others are local to their category. The global parameter vector
+/// lists the parameters in order of first appearance.
+#[derive(Default)]
+pub struct SimultaneousLikelihood<'a> {
+    categories: Vec<Category<'a>>,
+    parameters: Vec<Parameter>,
+}
+
+impl<'a> SimultaneousLikelihood<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a category. The starting value, step and limits of a shared
+    /// parameter are taken from the first model it appears in.
+    pub fn with_category(mut self, name: &str, model: &'a dyn Model, data: &'a Dataset) -> Self {
+        assert!(
+            self.categories.iter().all(|c| c.name != name),
+            "duplicate category `{}`",
+            name
+        );
+        let indices = model
+            .parameters()
+            .into_iter()
+            .map(|parameter| {
+                match self
+                    .parameters
+                    .iter()
+                    .position(|p| p.name() == parameter.name())
+                {
+                    Some(index) => index,
+                    None => {
+                        self.parameters.push(parameter);
+                        self.parameters.len() - 1
+                    }
+                }
+            })
+            .collect();
+        self.categories.push(Category {
+            name: name.to_string(),
+            likelihood: NegativeLogLikelihood::new(model, data),
+            indices,
+        });
+        self
+    }
+
+    pub fn categories(&self) -> Vec<&str> {
+        self.categories.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    /// Global indices of the named category's parameters, in the model's
+    /// layout order.
+    pub fn category_parameters(&self, name: &str) -> Option<&[usize]> {
+        self.categories
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.indices.as_slice())
+    }
+
+    pub fn parameters(&self) -> Vec<Parameter> {
+        self.parameters.clone()
+    }
+}
+
+impl Objective for SimultaneousLikelihood<'_> {
+    fn dimension(&self) -> usize {
+        self.parameters.len()
+    }
+
+    fn error_definition(&self) -> Float {
+        0.5
+    }
+
+    fn value_and_gradient(&self, parameters: &[Float]) -> (Float, Vec<Float>) {
+        let mut value = 0.0;
+        let mut gradient = vec![0.0; parameters.len()];
+        for category in &self.categories {
+            let (local_value, local_gradient) = category
+                .likelihood
+                .value_and_gradient(&category.local(parameters));
+            value += local_value;
+            for (&i, g) in category.indices.iter().zip(local_gradient) {
+                gradient[i] += g;
+            }
+        }
+        (value, gradient)
+    }
+
+    fn hessian(&self, parameters: &[Float]) -> Option<Matrix> {
+        let mut hessian = Matrix::zeros(parameters.len());
+        for category in &self.categories {
+            let local = category.likelihood.hessian(&category.local(parameters))?;
+            category.scatter(&local, &mut hessian);
+        }
+        Some(hessian)
+    }
+
+    /// Combines the categories' gradient covariances when any dataset is
+    /// weighted, using the Hessian of the unweighted categories, for which
+    /// the two agree.
+    fn gradient_covariance(&self, parameters: &[Float]) -> Option<Matrix> {
+        let locals: Vec<Option<Matrix>> = self
+            .categories
+            .iter()
+            .map(|c| c.likelihood.gradient_covariance(&c.local(parameters)))
+            .collect();
+        if locals.iter().all(Option::is_none) {
+            return None;
+        }
+        let mut covariance = Matrix::zeros(parameters.len());
+        for (category, local) in self.categories.iter().zip(locals) {
+            let local = local.unwrap_or_else(|| {
+                Hesse::new().hessian(&category.likelihood, &category.local(parameters))
+            });
+            category.scatter(&local, &mut covariance);
+        }
+        Some(covariance)
+    }
+}
@@ -0,0 +1,231 @@
+use intermediate_representation::Float;
+
+use crate::matrix::Matrix;
+use crate::minimize::Objective;
+use crate::parameter::Parameter;
+
+/// Mapping between a parameter's external value and the unbounded internal
+/// value seen by the minimizers, following MINUIT's conventions: a sine
+/// transformation for two-sided limits and a square-root transformation for
+/// one-sided limits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    Unbounded,
+    Lower(Float),
+    Upper(Float),
+    Double(Float, Float),
+}
+
+impl Transform {
+    pub fn from_limits(lower: Option<Float>, upper: Option<Float>) -> Self {
+        match (lower, upper) {
+            (None, None) => Self::Unbounded,
+            (Some(lower), None) => Self::Lower(lower),
+            (None, Some(upper)) => Self::Upper(upper),
+            (Some(lower), Some(upper)) => Self::Double(lower, upper),
+        }
+    }
+
+    pub fn for_parameter(parameter: &Parameter) -> Self {
+        Self::from_limits(parameter.lower_limit(), parameter.upper_limit())
+    }
+
+    /// Internal value of `external`, which is clamped into the limits first.
+    pub fn to_internal(&self, external: Float) -> Float {
+        match *self {
+            Self::Unbounded => external,
+            Self::Lower(lower) => {
+                let shifted = (external - lower).max(0.0) + 1.0;
+                (shifted * shifted - 1.0).sqrt()
+            }
+            Self::Upper(upper) => {
+                let shifted = (upper - external).max(0.0) + 1.0;
+                (shifted * shifted - 1.0).sqrt()
+            }
+            Self::Double(lower, upper) => {
+                let scaled = 2.0 * (external - lower) / (upper - lower) - 1.0;
+                scaled.clamp(-1.0, 1.0).asin()
+            }
+        }
+    }
+
+    pub fn to_external(&self, internal: Float) -> Float {
+        match *self {
+            Self::Unbounded => internal,
+            Self::Lower(lower) => lower - 1.0 + (internal * internal + 1.0).sqrt(),
+            Self::Upper(upper) => upper + 1.0 - (internal * internal + 1.0).sqrt(),
+            Self::Double(lower, upper) => lower + 0.5 * (upper - lower) * (internal.sin() + 1.0),
+        }
+    }
+
+    /// Derivative of the external value with respect to the internal one.
+    pub fn derivative(&self, internal: Float) -> Float {
+        match *self {
+            Self::Unbounded => 1.0,
+            Self::Lower(_) => internal / (internal * internal + 1.0).sqrt(),
+            Self::Upper(_) => -internal / (internal * internal + 1.0).sqrt(),
+            Self::Double(lower, upper) => 0.5 * (upper - lower) * internal.cos(),
+        }
+    }
+
+    pub fn second_derivative(&self, internal: Float) -> Float {
+        match *self {
+            Self::Unbounded => 0.0,
+            Self::Lower(_) => (internal * internal + 1.0).powf(-1.5),
+            Self::Upper(_) => -(internal * internal + 1.0).powf(-1.5),
+            Self::Double(lower, upper) => -0.5 * (upper - lower) * internal.sin(),
+        }
+    }
+}
+
+/// Objective expressed in internal coordinates, with the gradient and Hessian
+/// chain-ruled through each parameter's transformation.
+pub struct Bounded<'a, O: Objective + ?Sized> {
+    objective: &'a O,
+    transforms: Vec<Transform>,
+}
+
+impl<'a, O: Objective + ?Sized> Bounded<'a, O> {
+    pub fn new(objective: &'a O, transforms: Vec<Transform>) -> Self {
+        assert_eq!(objective.dimension(), transforms.len());
+        Self {
+            objective,
+            transforms,
+        }
+    }
+
+    pub fn transforms(&self) -> &[Transform] {
+        &self.transforms
+    }
+
+    pub fn to_internal(&self, external: &[Float]) -> Vec<Float> {
+        self.transforms
+            .iter()
+            .zip(external)
+            .map(|(t, x)| t.to_internal(*x))
+            .collect()
+    }
+
+    pub fn to_external(&self, internal: &[Float]) -> Vec<Float> {
+        self.transforms
+            .iter()
+            .zip(internal)
+            .map(|(t, x)| t.to_external(*x))
+            .collect()
+    }
+
+    /// Diagonal of the Jacobian of the external values at `internal`.
+    pub fn jacobian(&self, internal: &[Float]) -> Vec<Float> {
+        self.transforms
+            .iter()
+            .zip(internal)
+            .map(|(t, x)| t.derivative(*x))
+            .collect()
+    }
+
+    /// Converts external step sizes into internal ones at `internal`.
+    pub fn internal_steps(&self, internal: &[Float], external_steps: &[Float]) -> Vec<Float> {
+        self.jacobian(internal)
+            .iter()
+            .zip(external_steps)
+            .map(|(d, step)| {
+                if d.abs() > Float::EPSILON {
+                    (step / d).abs().min(1.0)
+                } else {
+                    1.0
+                }
+            })
+            .collect()
+    }
+
+    /// Propagates an internal covariance matrix to external coordinates.
+    pub fn external_covariance(&self, internal: &[Float], covariance: &Matrix) -> Matrix {
+        let jacobian = self.jacobian(internal);
+        let mut external = covariance.clone();
+        for i in 0..jacobian.len() {
+            for j in 0..jacobian.len() {
+                external[(i, j)] *= jacobian[i] * jacobian[j];
+            }
+        }
+        external
+    }
+}
+
+impl<O: Objective + ?Sized> Objective for Bounded<'_, O> {
+    fn dimension(&self) -> usize {
+        self.transforms.len()
+    }
+
+    fn error_definition(&self) -> Float {
+        self.objective.error_definition()
+    }
+
+    fn value_and_gradient(&self, parameters: &[Float]) -> (Float, Vec<Float>) {
+        let (value, gradient) = self
+            .objective
+            .value_and_gradient(&self.to_external(parameters));
+        let gradient = gradient
+            .iter()
+            .zip(self.jacobian(parameters))
+            .map(|(g, d)| g * d)
+            .collect();
+        (value, gradient)
+    }
+
+    fn hessian(&self, parameters: &[Float]) -> Option<Matrix> {
+        let external = self.to_external(parameters);
+        let mut hessian = self.objective.hessian(&external)?;
+        let (_, gradient) = self.objective.value_and_gradient(&external);
+        let jacobian = self.jacobian(parameters);
+        for i in 0..jacobian.len() {
+            for j in 0..jacobian.len() {
+                hessian[(i, j)] *= jacobian[i] * jacobian[j];
+            }
+            hessian[(i, i)] += gradient[i] * self.transforms[i].second_derivative(parameters[i]);
+        }
+        Some(hessian)
+    }
+
+    fn gradient_covariance(&self, parameters: &[Float]) -> Option<Matrix> {
+        let mut covariance = self
+            .objective
+            .gradient_covariance(&self.to_external(parameters))?;
+        let jacobian = self.jacobian(parameters);
+        for i in 0..jacobian.len() {
+            for j in 0..jacobian.len() {
+                covariance[(i, j)] *= jacobian[i] * jacobian[j];
+            }
+        }
+        Some(covariance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_derivatives() {
+        let transforms = [
+            Transform::Unbounded,
+            Transform::Lower(0.5),
+            Transform::Upper(-2.0),
+            Transform::Double(-1.0, 3.0),
+        ];
+        let externals = [1.7, 2.5, -4.0, 0.3];
+        for (transform, external) in transforms.iter().zip(externals) {
+            let internal = transform.to_internal(external);
+            assert!((transform.to_external(internal) - external).abs() < 1e-12);
+
+            let step = 1e-6;
+            let numerical = (transform.to_external(internal + step)
+                - transform.to_external(internal - step))
+                / (2.0 * step);
+            assert!((transform.derivative(internal) - numerical).abs() < 1e-6);
+            let numerical = (transform.derivative(internal + step)
+                - transform.derivative(internal - step))
+                / (2.0 * step);
+            assert!((transform.second_derivative(internal) - numerical).abs() < 1e-6);
+        }
+    }
+}
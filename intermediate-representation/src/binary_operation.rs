@@ -1,6 +1,11 @@
 use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
 
+use crate::builtin::Builtin;
+use crate::constant::Constant;
+use crate::expression::{ExpressionGraph, Node, NodeId};
+use crate::{Float, FloatConsts, special};
+
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum BinaryOperation {
     Add,
@@ -9,9 +14,54 @@ pub enum BinaryOperation {
     Div,
     PowI,
     PowF,
+    Atan2,
+    Hypot,
+    Min,
+    Max,
+    /// The regularized lower incomplete gamma function `P(a, x)`, with `a` on
+    /// the left.
+    GammaP,
+    /// The regularized upper incomplete gamma function `Q(a, x)`.
+    GammaQ,
+    /// Real and imaginary parts of the Faddeeva function `w(x + iy)`, for
+    /// `y > 0`.
+    FaddeevaRe,
+    FaddeevaIm,
+    /// Comparisons and logical connectives, valued one when true and zero
+    /// when false. Nonzero operands of `And` and `Or` count as true.
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+fn indicator(test: bool) -> Float {
+    if test { 1.0 } else { 0.0 }
 }
 
 impl BinaryOperation {
+    /// The operation written as the method `left.name(right)` or the function
+    /// `name(left, right)`.
+    pub fn rust_mappings(name: &str) -> Option<Self> {
+        match name {
+            "powi" => Some(Self::PowI),
+            "powf" => Some(Self::PowF),
+            "atan2" => Some(Self::Atan2),
+            "hypot" => Some(Self::Hypot),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            "gamma_p" => Some(Self::GammaP),
+            "gamma_q" => Some(Self::GammaQ),
+            "faddeeva_re" => Some(Self::FaddeevaRe),
+            "faddeeva_im" => Some(Self::FaddeevaIm),
+            _ => None,
+        }
+    }
+
     pub fn generate_forward(
         &self,
         result: Ident,
@@ -38,6 +88,58 @@ impl BinaryOperation {
             Self::PowF => {
                 quote! { let #result = #left_value.powf(#right_value as #num); }
             }
+            Self::Atan2 => {
+                quote! { let #result = #left_value.atan2(#right_value); }
+            }
+            Self::Hypot => {
+                quote! { let #result = #left_value.hypot(#right_value); }
+            }
+            Self::Min => {
+                quote! { let #result = #left_value.min(#right_value); }
+            }
+            Self::Max => {
+                quote! { let #result = #left_value.max(#right_value); }
+            }
+            Self::GammaP => {
+                quote! {
+                    let #result = ::intermediate_representation::special::gamma_p(#left_value, #right_value);
+                }
+            }
+            Self::GammaQ => {
+                quote! {
+                    let #result = ::intermediate_representation::special::gamma_q(#left_value, #right_value);
+                }
+            }
+            Self::FaddeevaRe => {
+                quote! {
+                    let #result = ::intermediate_representation::special::faddeeva_re(#left_value, #right_value);
+                }
+            }
+            Self::FaddeevaIm => {
+                quote! {
+                    let #result = ::intermediate_representation::special::faddeeva_im(#left_value, #right_value);
+                }
+            }
+            Self::Lt
+            | Self::Le
+            | Self::Gt
+            | Self::Ge
+            | Self::Eq
+            | Self::Ne
+            | Self::And
+            | Self::Or => {
+                let test = match self {
+                    Self::Lt => quote! { #left_value < #right_value },
+                    Self::Le => quote! { #left_value <= #right_value },
+                    Self::Gt => quote! { #left_value > #right_value },
+                    Self::Ge => quote! { #left_value >= #right_value },
+                    Self::Eq => quote! { #left_value == #right_value },
+                    Self::Ne => quote! { #left_value != #right_value },
+                    Self::And => quote! { #left_value != 0.0 && #right_value != 0.0 },
+                    _ => quote! { #left_value != 0.0 || #right_value != 0.0 },
+                };
+                quote! { let #result: #num = if #test { 1.0 } else { 0.0 }; }
+            }
         }
     }
     pub fn generate_reverse(
@@ -78,7 +180,252 @@ impl BinaryOperation {
                 quote! { #left_adj += #propagate * #right_value as #num * #left_value.powi(#right_value - 1); }
             }
             Self::PowF => {
-                quote! { #left_adj += #propagate * #right_value * #left_value.powf(#right_value - 1.0 as #num); }
+                quote! {
+                    #left_adj += #propagate * #right_value * #left_value.powf(#right_value - 1.0 as #num);
+                    #right_adj += #propagate * #left_value.ln() * #left_value.powf(#right_value);
+                }
+            }
+            Self::Atan2 => {
+                quote! {
+                    #left_adj += #propagate * #right_value
+                        / (#left_value * #left_value + #right_value * #right_value);
+                    #right_adj -= #propagate * #left_value
+                        / (#left_value * #left_value + #right_value * #right_value);
+                }
+            }
+            Self::Hypot => {
+                quote! {
+                    #left_adj += #propagate * #left_value / #left_value.hypot(#right_value);
+                    #right_adj += #propagate * #right_value / #left_value.hypot(#right_value);
+                }
+            }
+            // Ties split the adjoint evenly, matching the symbolic partials.
+            Self::Min | Self::Max => {
+                let (first, second) = match self {
+                    Self::Min => (&left_adj, &right_adj),
+                    _ => (&right_adj, &left_adj),
+                };
+                quote! {
+                    if #left_value < #right_value {
+                        #first += #propagate;
+                    } else if #left_value > #right_value {
+                        #second += #propagate;
+                    } else {
+                        #left_adj += 0.5 * #propagate;
+                        #right_adj += 0.5 * #propagate;
+                    }
+                }
+            }
+            // Only `x` is differentiated, see `partials`.
+            Self::GammaP | Self::GammaQ => {
+                let density = quote! {
+                    #propagate
+                        * ((#left_value - 1.0) * #right_value.ln() - #right_value
+                            - ::intermediate_representation::special::ln_gamma(#left_value))
+                            .exp()
+                };
+                match self {
+                    Self::GammaP => quote! { #right_adj += #density; },
+                    _ => quote! { #right_adj -= #density; },
+                }
+            }
+            // With w'(z) = -2 z w + 2i / sqrt(pi) = a + ib, see `partials`.
+            Self::FaddeevaRe | Self::FaddeevaIm => {
+                let update = match self {
+                    Self::FaddeevaRe => quote! {
+                        #left_adj += #propagate * a;
+                        #right_adj -= #propagate * b;
+                    },
+                    _ => quote! {
+                        #left_adj += #propagate * b;
+                        #right_adj += #propagate * a;
+                    },
+                };
+                quote! {
+                    {
+                        let (re, im) =
+                            ::intermediate_representation::special::faddeeva(#left_value, #right_value);
+                        let a = -2.0 * (#left_value * re - #right_value * im);
+                        let b = 2.0 / Float::PI.sqrt() - 2.0 * (#left_value * im + #right_value * re);
+                        #update
+                    }
+                }
+            }
+            Self::Lt
+            | Self::Le
+            | Self::Gt
+            | Self::Ge
+            | Self::Eq
+            | Self::Ne
+            | Self::And
+            | Self::Or => {
+                quote! {}
+            }
+        }
+    }
+
+    /// Value of the operation applied to `left` and `right`.
+    pub fn apply(&self, left: Float, right: Float) -> Float {
+        match &self {
+            Self::Add => left + right,
+            Self::Sub => left - right,
+            Self::Mul => left * right,
+            Self::Div => left / right,
+            Self::PowI => left.powi(right as i32),
+            Self::PowF => left.powf(right),
+            Self::Atan2 => left.atan2(right),
+            Self::Hypot => left.hypot(right),
+            Self::Min => left.min(right),
+            Self::Max => left.max(right),
+            Self::GammaP => special::gamma_p(left, right),
+            Self::GammaQ => special::gamma_q(left, right),
+            Self::FaddeevaRe => special::faddeeva_re(left, right),
+            Self::FaddeevaIm => special::faddeeva_im(left, right),
+            Self::Lt => indicator(left < right),
+            Self::Le => indicator(left <= right),
+            Self::Gt => indicator(left > right),
+            Self::Ge => indicator(left >= right),
+            Self::Eq => indicator(left == right),
+            Self::Ne => indicator(left != right),
+            Self::And => indicator(left != 0.0 && right != 0.0),
+            Self::Or => indicator(left != 0.0 || right != 0.0),
+        }
+    }
+
+    /// Inserts the partial derivatives with respect to the left and right
+    /// operands into `graph`. The right partial is `None` when the operand is
+    /// discrete, as for the integer exponent of `powi`.
+    pub fn partials(
+        &self,
+        graph: &mut ExpressionGraph,
+        left: NodeId,
+        right: NodeId,
+        result: NodeId,
+    ) -> (NodeId, Option<NodeId>) {
+        match &self {
+            Self::Add => {
+                let one = graph.constant(1.0);
+                (one, Some(one))
+            }
+            Self::Sub => {
+                let one = graph.constant(1.0);
+                let minus_one = graph.constant(-1.0);
+                (one, Some(minus_one))
+            }
+            Self::Mul => (right, Some(left)),
+            Self::Div => {
+                let one = graph.constant(1.0);
+                let left_partial = graph.div(one, right);
+                let quotient = graph.div(result, right);
+                (left_partial, Some(graph.neg(quotient)))
+            }
+            Self::PowI => {
+                let Node::Constant(Constant::Integer(exponent)) = graph.get_node(right) else {
+                    panic!(
+                        "the exponent of `powi` must be an integer literal to be differentiated"
+                    );
+                };
+                let coefficient = graph.constant(exponent as Float);
+                let power = match exponent - 1 {
+                    0 => graph.constant(1.0),
+                    1 => left,
+                    reduced => {
+                        let reduced = graph.insert(Node::new_integer(reduced));
+                        graph.insert(Node::new_binary_operation(Self::PowI, left, reduced))
+                    }
+                };
+                (graph.mul(coefficient, power), None)
+            }
+            Self::PowF => {
+                let one = graph.constant(1.0);
+                let reduced = graph.sub(right, one);
+                let power = graph.insert(Node::new_binary_operation(Self::PowF, left, reduced));
+                let left_partial = graph.mul(right, power);
+                let log = graph.builtin(Builtin::Log, left);
+                (left_partial, Some(graph.mul(log, result)))
+            }
+            Self::Atan2 => {
+                let left_square = graph.mul(left, left);
+                let right_square = graph.mul(right, right);
+                let radius = graph.add(left_square, right_square);
+                let left_partial = graph.div(right, radius);
+                let quotient = graph.div(left, radius);
+                (left_partial, Some(graph.neg(quotient)))
+            }
+            Self::Hypot => (graph.div(left, result), Some(graph.div(right, result))),
+            Self::Min | Self::Max => {
+                // With s the sign of `left - right`, the partials of `min` are
+                // `(1 - s) / 2` and `(1 + s) / 2`, and those of `max` swapped.
+                let difference = graph.sub(left, right);
+                let sign = graph.builtin(Builtin::Sign, difference);
+                let half = graph.constant(0.5);
+                let one = graph.constant(1.0);
+                let below = graph.sub(one, sign);
+                let above = graph.add(one, sign);
+                let below = graph.mul(half, below);
+                let above = graph.mul(half, above);
+                match self {
+                    Self::Min => (below, Some(above)),
+                    _ => (above, Some(below)),
+                }
+            }
+            Self::GammaP | Self::GammaQ => {
+                // d/dx P(a, x) = x^(a - 1) e^-x / Gamma(a). The partial with
+                // respect to `a` is never needed, as the parser rejects an `a`
+                // that depends on parameters.
+                let one = graph.constant(1.0);
+                let reduced = graph.sub(left, one);
+                let log = graph.builtin(Builtin::Log, right);
+                let power = graph.mul(reduced, log);
+                let decay = graph.sub(power, right);
+                let gamma = graph.builtin(Builtin::LnGamma, left);
+                let exponent = graph.sub(decay, gamma);
+                let mut density = graph.builtin(Builtin::Exp, exponent);
+                if *self == Self::GammaQ {
+                    density = graph.neg(density);
+                }
+                (graph.constant(0.0), Some(density))
+            }
+            // w'(z) = -2 z w + 2i / sqrt(pi) = a + ib, so that by the
+            // Cauchy-Riemann equations the partials of Re w are a and -b, and
+            // those of Im w are b and a.
+            Self::FaddeevaRe | Self::FaddeevaIm => {
+                let (re, im) = match self {
+                    Self::FaddeevaRe => (
+                        result,
+                        graph.insert(Node::new_binary_operation(Self::FaddeevaIm, left, right)),
+                    ),
+                    _ => (
+                        graph.insert(Node::new_binary_operation(Self::FaddeevaRe, left, right)),
+                        result,
+                    ),
+                };
+                let minus_two = graph.constant(-2.0);
+                let x_re = graph.mul(left, re);
+                let y_im = graph.mul(right, im);
+                let real = graph.sub(x_re, y_im);
+                let a = graph.mul(minus_two, real);
+                let x_im = graph.mul(left, im);
+                let y_re = graph.mul(right, re);
+                let imaginary = graph.add(x_im, y_re);
+                let imaginary = graph.mul(minus_two, imaginary);
+                let offset = graph.constant(2.0 / Float::PI.sqrt());
+                let b = graph.add(imaginary, offset);
+                match self {
+                    Self::FaddeevaRe => (a, Some(graph.neg(b))),
+                    _ => (b, Some(a)),
+                }
+            }
+            Self::Lt
+            | Self::Le
+            | Self::Gt
+            | Self::Ge
+            | Self::Eq
+            | Self::Ne
+            | Self::And
+            | Self::Or => {
+                let zero = graph.constant(0.0);
+                (zero, Some(zero))
             }
         }
     }
@@ -0,0 +1,74 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::binary_operation::BinaryOperation;
+use crate::expression::{ExpressionGraph, Node, NodeId};
+
+/// The branches under which a node is needed, as `(select, taken)` pairs that
+/// must all hold, `taken` being whether the condition of the select is
+/// nonzero.
+pub type Guard = BTreeSet<(NodeId, bool)>;
+
+impl ExpressionGraph {
+    /// The guard of every node reachable from `outputs`. A node needed by
+    /// several parents keeps the branches common to all of them, so it is
+    /// needed whenever its guard holds.
+    pub fn guards(&self, outputs: &[NodeId]) -> HashMap<NodeId, Guard> {
+        let mut guards: HashMap<NodeId, Guard> =
+            outputs.iter().map(|&id| (id, Guard::new())).collect();
+        for id in self.topological_sort_all(outputs).into_iter().rev() {
+            let guard = guards[&id].clone();
+            let branches = match self.get_node(id) {
+                Node::Select(condition, then, otherwise) => {
+                    vec![
+                        (condition, None),
+                        (then, Some(true)),
+                        (otherwise, Some(false)),
+                    ]
+                }
+                _ => self
+                    .get_children(id)
+                    .into_iter()
+                    .map(|c| (c, None))
+                    .collect(),
+            };
+            for (child, branch) in branches {
+                let mut edge = guard.clone();
+                if let Some(taken) = branch {
+                    edge.insert((id, taken));
+                }
+                guards
+                    .entry(child)
+                    .and_modify(|existing| existing.retain(|b| edge.contains(b)))
+                    .or_insert(edge);
+            }
+        }
+        guards
+    }
+
+    /// Inserts a node that is one when all branches of `guard` are taken and
+    /// zero otherwise.
+    pub fn guard_condition(&mut self, guard: &Guard) -> NodeId {
+        let zero = self.constant(0.0);
+        let mut condition = None;
+        for &(select, taken) in guard {
+            let Node::Select(test, _, _) = self.get_node(select) else {
+                panic!("guard branch {} is not a select", select);
+            };
+            let binop = if taken {
+                BinaryOperation::Ne
+            } else {
+                BinaryOperation::Eq
+            };
+            let branch = self.insert(Node::new_binary_operation(binop, test, zero));
+            condition = Some(match condition {
+                Some(condition) => self.insert(Node::new_binary_operation(
+                    BinaryOperation::And,
+                    condition,
+                    branch,
+                )),
+                None => branch,
+            });
+        }
+        condition.unwrap_or_else(|| self.constant(1.0))
+    }
+}
@@ -1,6 +1,10 @@
 use proc_macro2::{Ident, TokenStream};
 use quote::quote;
 
+use crate::binary_operation::BinaryOperation;
+use crate::expression::{ExpressionGraph, Node, NodeId};
+use crate::{Float, FloatConsts, special};
+
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum Builtin {
     Sin,
@@ -8,6 +12,27 @@ pub enum Builtin {
     Tan,
     Exp,
     Log,
+    Sqrt,
+    Abs,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Log1p,
+    Expm1,
+    Log10,
+    Asinh,
+    /// The sign of the argument, zero at zero. Introduced as the derivative of
+    /// `abs`, `min` and `max` rather than written in models, as `signum` is one
+    /// at zero.
+    Sign,
+    Erf,
+    Erfc,
+    LnGamma,
+    /// The polygamma function of the given order, digamma being order zero.
+    Polygamma(u32),
+    BesselI0,
+    BesselI1,
 }
 
 impl Builtin {
@@ -18,6 +43,23 @@ impl Builtin {
             "tan" => Some(Self::Tan),
             "exp" => Some(Self::Exp),
             "ln" => Some(Self::Log),
+            "sqrt" => Some(Self::Sqrt),
+            "abs" => Some(Self::Abs),
+            "atan" => Some(Self::Atan),
+            "sinh" => Some(Self::Sinh),
+            "cosh" => Some(Self::Cosh),
+            "tanh" => Some(Self::Tanh),
+            "ln_1p" => Some(Self::Log1p),
+            "exp_m1" => Some(Self::Expm1),
+            "log10" => Some(Self::Log10),
+            "asinh" => Some(Self::Asinh),
+            "erf" => Some(Self::Erf),
+            "erfc" => Some(Self::Erfc),
+            "ln_gamma" => Some(Self::LnGamma),
+            "digamma" => Some(Self::Polygamma(0)),
+            "trigamma" => Some(Self::Polygamma(1)),
+            "bessel_i0" => Some(Self::BesselI0),
+            "bessel_i1" => Some(Self::BesselI1),
             _ => None,
         }
     }
@@ -39,6 +81,67 @@ impl Builtin {
             Self::Log => {
                 quote! { let #result = #argument_value.ln(); }
             }
+            Self::Sqrt => {
+                quote! { let #result = #argument_value.sqrt(); }
+            }
+            Self::Abs => {
+                quote! { let #result = #argument_value.abs(); }
+            }
+            Self::Atan => {
+                quote! { let #result = #argument_value.atan(); }
+            }
+            Self::Sinh => {
+                quote! { let #result = #argument_value.sinh(); }
+            }
+            Self::Cosh => {
+                quote! { let #result = #argument_value.cosh(); }
+            }
+            Self::Tanh => {
+                quote! { let #result = #argument_value.tanh(); }
+            }
+            Self::Log1p => {
+                quote! { let #result = #argument_value.ln_1p(); }
+            }
+            Self::Expm1 => {
+                quote! { let #result = #argument_value.exp_m1(); }
+            }
+            Self::Log10 => {
+                quote! { let #result = #argument_value.log10(); }
+            }
+            Self::Asinh => {
+                quote! { let #result = #argument_value.asinh(); }
+            }
+            Self::Sign => {
+                quote! {
+                    let #result: Float = if #argument_value > 0.0 {
+                        1.0
+                    } else if #argument_value < 0.0 {
+                        -1.0
+                    } else {
+                        0.0
+                    };
+                }
+            }
+            Self::Erf => {
+                quote! { let #result = ::intermediate_representation::special::erf(#argument_value); }
+            }
+            Self::Erfc => {
+                quote! { let #result = ::intermediate_representation::special::erfc(#argument_value); }
+            }
+            Self::LnGamma => {
+                quote! { let #result = ::intermediate_representation::special::ln_gamma(#argument_value); }
+            }
+            Self::Polygamma(order) => {
+                quote! {
+                    let #result = ::intermediate_representation::special::polygamma(#order, #argument_value);
+                }
+            }
+            Self::BesselI0 => {
+                quote! { let #result = ::intermediate_representation::special::bessel_i0(#argument_value); }
+            }
+            Self::BesselI1 => {
+                quote! { let #result = ::intermediate_representation::special::bessel_i1(#argument_value); }
+            }
         }
     }
     pub fn generate_reverse(
@@ -63,6 +166,210 @@ impl Builtin {
             Self::Log => {
                 quote! { #argument_adj += #propagate / #argument_value; }
             }
+            Self::Sqrt => {
+                quote! { #argument_adj += #propagate * 0.5 / #argument_value.sqrt(); }
+            }
+            Self::Abs => {
+                quote! {
+                    if #argument_value > 0.0 {
+                        #argument_adj += #propagate;
+                    } else if #argument_value < 0.0 {
+                        #argument_adj -= #propagate;
+                    }
+                }
+            }
+            Self::Atan => {
+                quote! { #argument_adj += #propagate / (1.0 + #argument_value * #argument_value); }
+            }
+            Self::Sinh => {
+                quote! { #argument_adj += #propagate * #argument_value.cosh(); }
+            }
+            Self::Cosh => {
+                quote! { #argument_adj += #propagate * #argument_value.sinh(); }
+            }
+            Self::Tanh => {
+                quote! { #argument_adj += #propagate * #argument_value.cosh().powi(-2); }
+            }
+            Self::Log1p => {
+                quote! { #argument_adj += #propagate / (1.0 + #argument_value); }
+            }
+            Self::Expm1 => {
+                quote! { #argument_adj += #propagate * #argument_value.exp(); }
+            }
+            Self::Log10 => {
+                quote! { #argument_adj += #propagate / (#argument_value * (10.0 as Float).ln()); }
+            }
+            Self::Asinh => {
+                quote! { #argument_adj += #propagate / (1.0 + #argument_value * #argument_value).sqrt(); }
+            }
+            Self::Sign => quote! {},
+            Self::Erf => {
+                quote! {
+                    #argument_adj += #propagate * 2.0 / Float::PI.sqrt()
+                        * (-#argument_value * #argument_value).exp();
+                }
+            }
+            Self::Erfc => {
+                quote! {
+                    #argument_adj -= #propagate * 2.0 / Float::PI.sqrt()
+                        * (-#argument_value * #argument_value).exp();
+                }
+            }
+            Self::LnGamma => {
+                quote! {
+                    #argument_adj += #propagate
+                        * ::intermediate_representation::special::digamma(#argument_value);
+                }
+            }
+            Self::Polygamma(order) => {
+                let next = order + 1;
+                quote! {
+                    #argument_adj += #propagate
+                        * ::intermediate_representation::special::polygamma(#next, #argument_value);
+                }
+            }
+            Self::BesselI0 => {
+                quote! {
+                    #argument_adj += #propagate
+                        * ::intermediate_representation::special::bessel_i1(#argument_value);
+                }
+            }
+            Self::BesselI1 => {
+                quote! {
+                    #argument_adj += #propagate
+                        * if #argument_value == 0.0 {
+                            0.5
+                        } else {
+                            ::intermediate_representation::special::bessel_i0(#argument_value)
+                                - ::intermediate_representation::special::bessel_i1(#argument_value)
+                                    / #argument_value
+                        };
+                }
+            }
+        }
+    }
+
+    /// Value of the builtin at `argument`.
+    pub fn apply(&self, argument: Float) -> Float {
+        match &self {
+            Self::Sin => argument.sin(),
+            Self::Cos => argument.cos(),
+            Self::Tan => argument.tan(),
+            Self::Exp => argument.exp(),
+            Self::Log => argument.ln(),
+            Self::Sqrt => argument.sqrt(),
+            Self::Abs => argument.abs(),
+            Self::Atan => argument.atan(),
+            Self::Sinh => argument.sinh(),
+            Self::Cosh => argument.cosh(),
+            Self::Tanh => argument.tanh(),
+            Self::Log1p => argument.ln_1p(),
+            Self::Expm1 => argument.exp_m1(),
+            Self::Log10 => argument.log10(),
+            Self::Asinh => argument.asinh(),
+            Self::Sign => {
+                if argument > 0.0 {
+                    1.0
+                } else if argument < 0.0 {
+                    -1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Erf => special::erf(argument),
+            Self::Erfc => special::erfc(argument),
+            Self::LnGamma => special::ln_gamma(argument),
+            Self::Polygamma(order) => special::polygamma(*order, argument),
+            Self::BesselI0 => special::bessel_i0(argument),
+            Self::BesselI1 => special::bessel_i1(argument),
+        }
+    }
+
+    /// Inserts the derivative of the builtin with respect to its argument into
+    /// `graph`, given the ids of the argument and of the builtin's own value.
+    pub fn derivative(
+        &self,
+        graph: &mut ExpressionGraph,
+        argument: NodeId,
+        result: NodeId,
+    ) -> NodeId {
+        match &self {
+            Self::Sin => graph.builtin(Self::Cos, argument),
+            Self::Cos => {
+                let sin = graph.builtin(Self::Sin, argument);
+                graph.neg(sin)
+            }
+            Self::Tan => {
+                let one = graph.constant(1.0);
+                let square = graph.mul(result, result);
+                graph.add(one, square)
+            }
+            Self::Exp => result,
+            Self::Log => {
+                let one = graph.constant(1.0);
+                graph.div(one, argument)
+            }
+            Self::Sqrt => {
+                let half = graph.constant(0.5);
+                graph.div(half, result)
+            }
+            Self::Abs => graph.builtin(Self::Sign, argument),
+            Self::Atan => {
+                let one = graph.constant(1.0);
+                let square = graph.mul(argument, argument);
+                let denominator = graph.add(one, square);
+                graph.div(one, denominator)
+            }
+            Self::Sinh => graph.builtin(Self::Cosh, argument),
+            Self::Cosh => graph.builtin(Self::Sinh, argument),
+            Self::Tanh => {
+                let one = graph.constant(1.0);
+                let square = graph.mul(result, result);
+                graph.sub(one, square)
+            }
+            Self::Log1p => {
+                let one = graph.constant(1.0);
+                let denominator = graph.add(one, argument);
+                graph.div(one, denominator)
+            }
+            Self::Expm1 => graph.builtin(Self::Exp, argument),
+            Self::Log10 => {
+                let scale = graph.constant(1.0 / (10.0 as Float).ln());
+                graph.div(scale, argument)
+            }
+            Self::Asinh => {
+                let one = graph.constant(1.0);
+                let square = graph.mul(argument, argument);
+                let sum = graph.add(one, square);
+                let root = graph.builtin(Self::Sqrt, sum);
+                graph.div(one, root)
+            }
+            Self::Sign => graph.constant(0.0),
+            Self::Erf | Self::Erfc => {
+                let sign = if *self == Self::Erf { 1.0 } else { -1.0 };
+                let scale = graph.constant(sign * 2.0 / Float::PI.sqrt());
+                let square = graph.mul(argument, argument);
+                let exponent = graph.neg(square);
+                let gaussian = graph.builtin(Self::Exp, exponent);
+                graph.mul(scale, gaussian)
+            }
+            Self::LnGamma => graph.builtin(Self::Polygamma(0), argument),
+            Self::Polygamma(order) => graph.builtin(Self::Polygamma(order + 1), argument),
+            Self::BesselI0 => graph.builtin(Self::BesselI1, argument),
+            // I1'(x) = I0(x) - I1(x) / x, with the limit 1 / 2 taken at zero.
+            Self::BesselI1 => {
+                let zero = graph.constant(0.0);
+                let at_zero = graph.insert(Node::new_binary_operation(
+                    BinaryOperation::Eq,
+                    argument,
+                    zero,
+                ));
+                let i0 = graph.builtin(Self::BesselI0, argument);
+                let quotient = graph.div(result, argument);
+                let derivative = graph.sub(i0, quotient);
+                let limit = graph.constant(0.5);
+                graph.select(at_zero, limit, derivative)
+            }
         }
     }
 }
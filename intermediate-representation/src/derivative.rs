@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::expression::{ExpressionGraph, Node, NodeId};
+
+impl ExpressionGraph {
+    /// Inserts the partial derivatives of node `id` with respect to each of its
+    /// children, returned as `(child, partial)` pairs.
+    fn partials(&mut self, id: NodeId) -> Vec<(NodeId, NodeId)> {
+        match self.get_node(id) {
+            Node::Constant(_) | Node::Variable(_) => vec![],
+            Node::Builtin(builtin, argument) => {
+                vec![(argument, builtin.derivative(self, argument, id))]
+            }
+            Node::BinaryOperation(binop, left, right) => {
+                let (left_partial, right_partial) = binop.partials(self, left, right, id);
+                let mut partials = vec![(left, left_partial)];
+                if let Some(right_partial) = right_partial {
+                    partials.push((right, right_partial));
+                }
+                partials
+            }
+            Node::Select(_, then, otherwise) => {
+                let one = self.constant(1.0);
+                vec![(then, one), (otherwise, one)]
+            }
+        }
+    }
+
+    /// Nodes reachable from `output` whose value depends on any of `variables`.
+    fn active_nodes(&self, sorted: &[NodeId], variables: &[NodeId]) -> HashSet<NodeId> {
+        let mut active: HashSet<NodeId> = variables.iter().copied().collect();
+        for &id in sorted {
+            if self.get_children(id).iter().any(|c| active.contains(c)) {
+                active.insert(id);
+            }
+        }
+        active
+    }
+
+    /// Reverse-mode symbolic differentiation: inserts the adjoint of every
+    /// active node and returns the derivative of `output` with respect to each
+    /// of `variables`. Contributions from within branches of selects are
+    /// themselves selected on the branches being taken, so that the partials
+    /// of an untaken branch, which may not be finite, are never evaluated.
+    pub fn gradient(&mut self, output: NodeId, variables: &[NodeId]) -> Vec<NodeId> {
+        let sorted = self.topological_sort(output);
+        let active = self.active_nodes(&sorted, variables);
+        let guards = self.guards(&[output]);
+
+        let mut adjoints: HashMap<NodeId, NodeId> = HashMap::new();
+        let one = self.constant(1.0);
+        adjoints.insert(output, one);
+        for &id in sorted.iter().rev() {
+            if !active.contains(&id) {
+                continue;
+            }
+            let Some(&adjoint) = adjoints.get(&id) else {
+                continue;
+            };
+            let node = self.get_node(id);
+            for (child, partial) in self.partials(id) {
+                if !active.contains(&child) {
+                    continue;
+                }
+                let mut guard = guards[&id].clone();
+                if let Node::Select(_, then, _) = node {
+                    guard.insert((id, child == then));
+                }
+                let mut contribution = self.mul(adjoint, partial);
+                if !guard.is_empty() {
+                    let condition = self.guard_condition(&guard);
+                    let zero = self.constant(0.0);
+                    contribution = self.select(condition, contribution, zero);
+                }
+                let total = match adjoints.get(&child) {
+                    Some(&existing) => self.add(existing, contribution),
+                    None => contribution,
+                };
+                adjoints.insert(child, total);
+            }
+        }
+
+        let zero = self.constant(0.0);
+        variables
+            .iter()
+            .map(|v| adjoints.get(v).copied().unwrap_or(zero))
+            .collect()
+    }
+
+    /// Forward-mode symbolic differentiation: returns the derivative of each
+    /// of `outputs` with respect to `variable`.
+    pub fn tangent(&mut self, outputs: &[NodeId], variable: NodeId) -> Vec<NodeId> {
+        let sorted = self.topological_sort_all(outputs);
+
+        let mut tangents: HashMap<NodeId, NodeId> = HashMap::new();
+        let one = self.constant(1.0);
+        tangents.insert(variable, one);
+        for &id in sorted.iter() {
+            if id == variable
+                || !self
+                    .get_children(id)
+                    .iter()
+                    .any(|c| tangents.contains_key(c))
+            {
+                continue;
+            }
+            if let Node::Select(condition, then, otherwise) = self.get_node(id) {
+                let zero = self.constant(0.0);
+                let then = tangents.get(&then).copied().unwrap_or(zero);
+                let otherwise = tangents.get(&otherwise).copied().unwrap_or(zero);
+                let tangent = self.select(condition, then, otherwise);
+                tangents.insert(id, tangent);
+                continue;
+            }
+            let mut total = None;
+            for (child, partial) in self.partials(id) {
+                let Some(&child_tangent) = tangents.get(&child) else {
+                    continue;
+                };
+                let contribution = self.mul(partial, child_tangent);
+                total = Some(match total {
+                    Some(existing) => self.add(existing, contribution),
+                    None => contribution,
+                });
+            }
+            if let Some(total) = total {
+                tangents.insert(id, total);
+            }
+        }
+
+        let zero = self.constant(0.0);
+        outputs
+            .iter()
+            .map(|o| tangents.get(o).copied().unwrap_or(zero))
+            .collect()
+    }
+
+    /// Second derivatives of `output` with respect to `variables`, computed
+    /// forward-over-reverse. Returns the gradient and the row-major Hessian.
+    pub fn hessian(
+        &mut self,
+        output: NodeId,
+        variables: &[NodeId],
+    ) -> (Vec<NodeId>, Vec<Vec<NodeId>>) {
+        let gradient = self.gradient(output, variables);
+        let hessian = variables
+            .iter()
+            .map(|&variable| self.tangent(&gradient, variable))
+            .collect();
+        (gradient, hessian)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::Float;
+    use crate::binary_operation::BinaryOperation;
+    use crate::builtin::Builtin;
+
+    /// Checks the symbolic gradient of `output` at `point` against central
+    /// differences of its value.
+    fn check(graph: &mut ExpressionGraph, output: NodeId, point: &[(&str, Float)]) {
+        let variables: Vec<NodeId> = point
+            .iter()
+            .map(|&(name, _)| graph.insert(Node::new_variable(name.to_string(), false)))
+            .collect();
+        let gradient = graph.gradient(output, &variables);
+        let values: HashMap<String, Float> =
+            point.iter().map(|&(n, v)| (n.to_string(), v)).collect();
+        for (i, &(name, value)) in point.iter().enumerate() {
+            let step = 1e-6 * value.abs().max(1.0);
+            let mut shifted = values.clone();
+            shifted.insert(name.to_string(), value + step);
+            let forward = graph.evaluate(output, &shifted);
+            shifted.insert(name.to_string(), value - step);
+            let backward = graph.evaluate(output, &shifted);
+            let numeric = (forward - backward) / (2.0 * step);
+            let analytic = graph.evaluate(gradient[i], &values);
+            assert!(
+                (analytic - numeric).abs() < 1e-6 * numeric.abs().max(1.0),
+                "d/d{}: {} != {}",
+                name,
+                analytic,
+                numeric
+            );
+        }
+    }
+
+    #[test]
+    fn builtin_rules() {
+        let builtins = [
+            (Builtin::Sqrt, 2.3),
+            (Builtin::Abs, -0.7),
+            (Builtin::Atan, 0.4),
+            (Builtin::Sinh, 0.9),
+            (Builtin::Cosh, -1.2),
+            (Builtin::Tanh, 0.6),
+            (Builtin::Log1p, 0.35),
+            (Builtin::Expm1, -0.25),
+            (Builtin::Log10, 3.7),
+            (Builtin::Asinh, -1.9),
+            (Builtin::Erf, 0.8),
+            (Builtin::Erfc, -0.6),
+            (Builtin::LnGamma, 2.7),
+            (Builtin::Polygamma(0), 0.45),
+            (Builtin::Polygamma(2), 3.1),
+            (Builtin::BesselI0, 1.7),
+            (Builtin::BesselI1, -2.4),
+            (Builtin::BesselI1, 0.0),
+        ];
+        for (builtin, at) in builtins {
+            let mut graph = ExpressionGraph::new();
+            let x = graph.insert(Node::new_variable("x".to_string(), false));
+            let output = graph.builtin(builtin, x);
+            check(&mut graph, output, &[("x", at)]);
+        }
+    }
+
+    #[test]
+    fn binary_rules() {
+        let operations = [
+            (BinaryOperation::Atan2, 0.7, -1.3),
+            (BinaryOperation::Hypot, -2.0, 0.5),
+            (BinaryOperation::Min, 1.5, 0.4),
+            (BinaryOperation::Min, -0.2, 0.4),
+            (BinaryOperation::Max, 1.5, 0.4),
+            (BinaryOperation::Max, -0.2, 0.4),
+            (BinaryOperation::FaddeevaRe, 0.7, 0.4),
+            (BinaryOperation::FaddeevaIm, -1.1, 0.6),
+        ];
+        for (binop, a, b) in operations {
+            let mut graph = ExpressionGraph::new();
+            let x = graph.insert(Node::new_variable("x".to_string(), false));
+            let y = graph.insert(Node::new_variable("y".to_string(), false));
+            // Composed with a builtin so the partials are chained.
+            let output = graph.insert(Node::new_binary_operation(binop, x, y));
+            let output = graph.builtin(Builtin::Sinh, output);
+            check(&mut graph, output, &[("x", a), ("y", b)]);
+        }
+
+        for binop in [BinaryOperation::GammaP, BinaryOperation::GammaQ] {
+            let mut graph = ExpressionGraph::new();
+            let shape = graph.constant(2.5);
+            let x = graph.insert(Node::new_variable("x".to_string(), false));
+            let output = graph.insert(Node::new_binary_operation(binop, shape, x));
+            check(&mut graph, output, &[("x", 1.8)]);
+        }
+    }
+
+    #[test]
+    fn select_differentiates_the_taken_branch() {
+        let mut graph = ExpressionGraph::new();
+        let x = graph.insert(Node::new_variable("x".to_string(), false));
+        let y = graph.insert(Node::new_variable("y".to_string(), false));
+        // if x > y { sqrt(x - y) } else { ln(y - x) y }, whose untaken branch
+        // has no finite derivative.
+        let condition = graph.insert(Node::new_binary_operation(BinaryOperation::Gt, x, y));
+        let above = graph.sub(x, y);
+        let root = graph.builtin(Builtin::Sqrt, above);
+        let below = graph.sub(y, x);
+        let log = graph.builtin(Builtin::Log, below);
+        let scaled = graph.mul(log, y);
+        let output = graph.select(condition, root, scaled);
+        check(&mut graph, output, &[("x", 1.5), ("y", 0.4)]);
+        check(&mut graph, output, &[("x", -0.5), ("y", 0.4)]);
+
+        let (gradient, hessian) = graph.hessian(output, &[x, y]);
+        let values: HashMap<String, Float> =
+            [("x".to_string(), 1.5), ("y".to_string(), 0.4)].into();
+        for id in gradient.into_iter().chain(hessian.into_iter().flatten()) {
+            assert!(graph.evaluate(id, &values).is_finite());
+        }
+    }
+}
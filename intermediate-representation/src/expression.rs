@@ -14,6 +14,10 @@ pub enum Node {
     Variable(Variable),
     Builtin(Builtin, NodeId),
     BinaryOperation(BinaryOperation, NodeId, NodeId),
+    /// `if condition != 0 { then } else { otherwise }`, with the condition,
+    /// then and otherwise ids in that order. Only the taken branch is
+    /// evaluated and differentiated.
+    Select(NodeId, NodeId, NodeId),
 }
 
 impl Node {
@@ -32,6 +36,9 @@ impl Node {
     pub fn new_binary_operation(binop: BinaryOperation, left: NodeId, right: NodeId) -> Node {
         Node::BinaryOperation(binop, left, right)
     }
+    pub fn new_select(condition: NodeId, then: NodeId, otherwise: NodeId) -> Node {
+        Node::Select(condition, then, otherwise)
+    }
 }
 
 #[derive(Debug)]
@@ -74,15 +81,193 @@ impl ExpressionGraph {
         self.nodes.len()
     }
 
-    fn get_children(&self, id: NodeId) -> Vec<NodeId> {
+    pub fn constant_value(&self, id: NodeId) -> Option<Float> {
+        match &self.nodes[id] {
+            Node::Constant(Constant::Float(value)) => Some(*value),
+            Node::Constant(Constant::Integer(value)) => Some(*value as Float),
+            _ => None,
+        }
+    }
+
+    /// Whether the value of `id` changes with any parameter, that is with a
+    /// variable that is not fixed.
+    pub fn depends_on_parameters(&self, id: NodeId) -> bool {
+        self.topological_sort(id)
+            .into_iter()
+            .any(|id| matches!(&self.nodes[id], Node::Variable(variable) if !variable.fixed))
+    }
+
+    fn is_constant(&self, id: NodeId, value: Float) -> bool {
+        self.constant_value(id) == Some(value)
+    }
+
+    fn float_constant(&self, id: NodeId) -> Option<Float> {
+        match &self.nodes[id] {
+            Node::Constant(Constant::Float(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn constant(&mut self, value: Float) -> NodeId {
+        self.insert(Node::new_float(value))
+    }
+
+    pub fn add(&mut self, left: NodeId, right: NodeId) -> NodeId {
+        if self.is_constant(left, 0.0) {
+            return right;
+        }
+        if self.is_constant(right, 0.0) {
+            return left;
+        }
+        if let (Some(l), Some(r)) = (self.float_constant(left), self.float_constant(right)) {
+            return self.constant(l + r);
+        }
+        self.insert(Node::new_binary_operation(
+            BinaryOperation::Add,
+            left,
+            right,
+        ))
+    }
+
+    pub fn sub(&mut self, left: NodeId, right: NodeId) -> NodeId {
+        if self.is_constant(right, 0.0) {
+            return left;
+        }
+        if let (Some(l), Some(r)) = (self.float_constant(left), self.float_constant(right)) {
+            return self.constant(l - r);
+        }
+        self.insert(Node::new_binary_operation(
+            BinaryOperation::Sub,
+            left,
+            right,
+        ))
+    }
+
+    pub fn neg(&mut self, argument: NodeId) -> NodeId {
+        let zero = self.constant(0.0);
+        self.sub(zero, argument)
+    }
+
+    pub fn mul(&mut self, left: NodeId, right: NodeId) -> NodeId {
+        if self.is_constant(left, 0.0) || self.is_constant(right, 0.0) {
+            return self.constant(0.0);
+        }
+        if self.is_constant(left, 1.0) {
+            return right;
+        }
+        if self.is_constant(right, 1.0) {
+            return left;
+        }
+        if let (Some(l), Some(r)) = (self.float_constant(left), self.float_constant(right)) {
+            return self.constant(l * r);
+        }
+        self.insert(Node::new_binary_operation(
+            BinaryOperation::Mul,
+            left,
+            right,
+        ))
+    }
+
+    pub fn div(&mut self, left: NodeId, right: NodeId) -> NodeId {
+        if self.is_constant(left, 0.0) {
+            return self.constant(0.0);
+        }
+        if self.is_constant(right, 1.0) {
+            return left;
+        }
+        if let (Some(l), Some(r)) = (self.float_constant(left), self.float_constant(right)) {
+            return self.constant(l / r);
+        }
+        self.insert(Node::new_binary_operation(
+            BinaryOperation::Div,
+            left,
+            right,
+        ))
+    }
+
+    pub fn builtin(&mut self, builtin: Builtin, argument: NodeId) -> NodeId {
+        self.insert(Node::new_builtin(builtin, argument))
+    }
+
+    pub fn powi(&mut self, base: NodeId, exponent: i32) -> NodeId {
+        match exponent {
+            0 => self.constant(1.0),
+            1 => base,
+            _ => {
+                let exponent = self.insert(Node::new_integer(exponent));
+                self.insert(Node::new_binary_operation(
+                    BinaryOperation::PowI,
+                    base,
+                    exponent,
+                ))
+            }
+        }
+    }
+
+    pub fn powf(&mut self, base: NodeId, exponent: NodeId) -> NodeId {
+        if self.is_constant(exponent, 1.0) {
+            return base;
+        }
+        self.insert(Node::new_binary_operation(
+            BinaryOperation::PowF,
+            base,
+            exponent,
+        ))
+    }
+
+    pub fn select(&mut self, condition: NodeId, then: NodeId, otherwise: NodeId) -> NodeId {
+        if then == otherwise {
+            return then;
+        }
+        if let Some(value) = self.constant_value(condition) {
+            return if value != 0.0 { then } else { otherwise };
+        }
+        self.insert(Node::new_select(condition, then, otherwise))
+    }
+
+    /// Evaluates the output node with the variables set to `values`, keyed
+    /// by name.
+    pub fn evaluate(&self, output: NodeId, values: &HashMap<String, Float>) -> Float {
+        let mut results: HashMap<NodeId, Float> = HashMap::new();
+        for id in self.topological_sort(output) {
+            let value = match &self.nodes[id] {
+                Node::Constant(_) => self.constant_value(id).unwrap(),
+                Node::Variable(variable) => *values
+                    .get(&variable.name)
+                    .unwrap_or_else(|| panic!("no value for variable `{}`", variable.name)),
+                Node::Builtin(builtin, argument) => builtin.apply(results[argument]),
+                Node::BinaryOperation(binop, left, right) => {
+                    binop.apply(results[left], results[right])
+                }
+                Node::Select(condition, then, otherwise) => {
+                    if results[condition] != 0.0 {
+                        results[then]
+                    } else {
+                        results[otherwise]
+                    }
+                }
+            };
+            results.insert(id, value);
+        }
+        results[&output]
+    }
+
+    pub fn get_children(&self, id: NodeId) -> Vec<NodeId> {
         match &self.nodes[id] {
             Node::BinaryOperation(_, left_id, right_id) => vec![*left_id, *right_id],
+            Node::Select(condition_id, then_id, otherwise_id) => {
+                vec![*condition_id, *then_id, *otherwise_id]
+            }
             Node::Builtin(_, argument_id) => vec![*argument_id],
             Node::Constant(_) | Node::Variable(_) => vec![],
         }
     }
 
     pub fn topological_sort(&self, start: NodeId) -> Vec<NodeId> {
+        self.topological_sort_all(&[start])
+    }
+
+    pub fn topological_sort_all(&self, starts: &[NodeId]) -> Vec<NodeId> {
         let mut visited = HashSet::new();
         let mut sorted = Vec::new();
 
@@ -103,7 +288,9 @@ impl ExpressionGraph {
             sorted.push(node_id);
         }
 
-        dfs(self, start, &mut visited, &mut sorted);
+        for &start in starts {
+            dfs(self, start, &mut visited, &mut sorted);
+        }
         sorted
     }
 }
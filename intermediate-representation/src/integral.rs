@@ -0,0 +1,438 @@
+use crate::binary_operation::BinaryOperation;
+use crate::builtin::Builtin;
+use crate::constant::Constant;
+use crate::expression::{ExpressionGraph, Node, NodeId};
+use crate::{Float, FloatConsts};
+
+/// Highest power of the variable tracked when expanding polynomials.
+const MAX_DEGREE: usize = 16;
+
+impl ExpressionGraph {
+    /// Whether the value of `expression` depends on `variable`.
+    pub fn depends_on(&self, expression: NodeId, variable: NodeId) -> bool {
+        self.topological_sort(expression).contains(&variable)
+    }
+
+    /// Expands `expression` as a polynomial in `variable`, returning the
+    /// coefficients in increasing order of the power, or `None` if it is not
+    /// one.
+    pub fn polynomial(&mut self, expression: NodeId, variable: NodeId) -> Option<Vec<NodeId>> {
+        if !self.depends_on(expression, variable) {
+            return Some(vec![expression]);
+        }
+        if expression == variable {
+            return Some(vec![self.constant(0.0), self.constant(1.0)]);
+        }
+        let Node::BinaryOperation(binop, left, right) = self.get_node(expression) else {
+            return None;
+        };
+        match binop {
+            BinaryOperation::Add | BinaryOperation::Sub => {
+                let left = self.polynomial(left, variable)?;
+                let right = self.polynomial(right, variable)?;
+                let mut coefficients = Vec::new();
+                for k in 0..left.len().max(right.len()) {
+                    let zero = self.constant(0.0);
+                    let l = left.get(k).copied().unwrap_or(zero);
+                    let r = right.get(k).copied().unwrap_or(zero);
+                    coefficients.push(match binop {
+                        BinaryOperation::Add => self.add(l, r),
+                        _ => self.sub(l, r),
+                    });
+                }
+                Some(coefficients)
+            }
+            BinaryOperation::Mul => {
+                let left = self.polynomial(left, variable)?;
+                let right = self.polynomial(right, variable)?;
+                self.multiply_polynomials(&left, &right)
+            }
+            BinaryOperation::Div if !self.depends_on(right, variable) => {
+                let left = self.polynomial(left, variable)?;
+                Some(left.into_iter().map(|c| self.div(c, right)).collect())
+            }
+            BinaryOperation::PowI => {
+                let Node::Constant(Constant::Integer(exponent)) = self.get_node(right) else {
+                    return None;
+                };
+                if exponent < 0 {
+                    return None;
+                }
+                let base = self.polynomial(left, variable)?;
+                let mut power = vec![self.constant(1.0)];
+                for _ in 0..exponent {
+                    power = self.multiply_polynomials(&power, &base)?;
+                }
+                Some(power)
+            }
+            _ => None,
+        }
+    }
+
+    fn multiply_polynomials(&mut self, left: &[NodeId], right: &[NodeId]) -> Option<Vec<NodeId>> {
+        let degree = left.len() + right.len() - 2;
+        if degree > MAX_DEGREE {
+            return None;
+        }
+        let mut product = vec![self.constant(0.0); degree + 1];
+        for (i, &l) in left.iter().enumerate() {
+            for (j, &r) in right.iter().enumerate() {
+                let term = self.mul(l, r);
+                product[i + j] = self.add(product[i + j], term);
+            }
+        }
+        Some(product)
+    }
+
+    /// Coefficients `(b, a)` of an expression `a + b x` linear in `variable`,
+    /// with `b` not identically zero.
+    fn linear(&mut self, expression: NodeId, variable: NodeId) -> Option<(NodeId, NodeId)> {
+        match self.polynomial(expression, variable)?[..] {
+            [constant, slope] if self.constant_value(slope) != Some(0.0) => Some((slope, constant)),
+            _ => None,
+        }
+    }
+
+    /// Symbolic antiderivative of `expression` with respect to `variable`,
+    /// covering polynomials, reciprocals of linear and quadratic polynomials
+    /// (the latter through `atan`), exponentials of linear and quadratic
+    /// arguments (the latter through `erf`), sines and cosines of linear
+    /// arguments and powers of linear bases, combined linearly. Returns `None`
+    /// when no rule applies.
+    pub fn antiderivative(&mut self, expression: NodeId, variable: NodeId) -> Option<NodeId> {
+        if !self.depends_on(expression, variable) {
+            return Some(self.mul(expression, variable));
+        }
+        if let Some(coefficients) = self.polynomial(expression, variable) {
+            let mut integral = self.constant(0.0);
+            for (k, coefficient) in coefficients.into_iter().enumerate() {
+                let power = self.powi(variable, k as i32 + 1);
+                let term = self.mul(coefficient, power);
+                let order = self.constant(k as Float + 1.0);
+                let term = self.div(term, order);
+                integral = self.add(integral, term);
+            }
+            return Some(integral);
+        }
+        match self.get_node(expression) {
+            Node::BinaryOperation(BinaryOperation::Add, left, right) => {
+                let left = self.antiderivative(left, variable)?;
+                let right = self.antiderivative(right, variable)?;
+                Some(self.add(left, right))
+            }
+            Node::BinaryOperation(BinaryOperation::Sub, left, right) => {
+                let left = self.antiderivative(left, variable)?;
+                let right = self.antiderivative(right, variable)?;
+                Some(self.sub(left, right))
+            }
+            Node::BinaryOperation(BinaryOperation::Mul, left, right) => {
+                if !self.depends_on(left, variable) {
+                    let right = self.antiderivative(right, variable)?;
+                    Some(self.mul(left, right))
+                } else if !self.depends_on(right, variable) {
+                    let left = self.antiderivative(left, variable)?;
+                    Some(self.mul(left, right))
+                } else {
+                    None
+                }
+            }
+            Node::BinaryOperation(BinaryOperation::Div, left, right) => {
+                if !self.depends_on(right, variable) {
+                    let left = self.antiderivative(left, variable)?;
+                    Some(self.div(left, right))
+                } else if !self.depends_on(left, variable) {
+                    if let Some((slope, _)) = self.linear(right, variable) {
+                        let log = self.log_abs(right);
+                        let scaled = self.div(left, slope);
+                        return Some(self.mul(scaled, log));
+                    }
+                    let [constant, slope, curvature] = self.polynomial(right, variable)?[..] else {
+                        return None;
+                    };
+                    let integral =
+                        self.reciprocal_quadratic_integral(constant, slope, curvature, variable)?;
+                    Some(self.mul(left, integral))
+                } else {
+                    None
+                }
+            }
+            Node::BinaryOperation(BinaryOperation::PowI, base, exponent) => {
+                let Node::Constant(Constant::Integer(exponent)) = self.get_node(exponent) else {
+                    return None;
+                };
+                let (slope, _) = self.linear(base, variable)?;
+                if exponent == -1 {
+                    let log = self.log_abs(base);
+                    return Some(self.div(log, slope));
+                }
+                let power = self.powi(base, exponent + 1);
+                let order = self.constant(exponent as Float + 1.0);
+                let denominator = self.mul(slope, order);
+                Some(self.div(power, denominator))
+            }
+            Node::BinaryOperation(BinaryOperation::PowF, base, exponent) => {
+                if !self.depends_on(exponent, variable) {
+                    let (slope, _) = self.linear(base, variable)?;
+                    if self.constant_value(exponent) == Some(-1.0) {
+                        let log = self.log_abs(base);
+                        return Some(self.div(log, slope));
+                    }
+                    let one = self.constant(1.0);
+                    let order = self.add(exponent, one);
+                    let power = self.powf(base, order);
+                    let denominator = self.mul(slope, order);
+                    Some(self.div(power, denominator))
+                } else if !self.depends_on(base, variable) {
+                    let (slope, _) = self.linear(exponent, variable)?;
+                    let log = self.builtin(Builtin::Log, base);
+                    let denominator = self.mul(slope, log);
+                    Some(self.div(expression, denominator))
+                } else {
+                    None
+                }
+            }
+            Node::Builtin(Builtin::Exp, argument) => match self.polynomial(argument, variable)?[..]
+            {
+                [_, slope] => Some(self.div(expression, slope)),
+                [constant, slope, curvature] => {
+                    self.gaussian_integral(constant, slope, curvature, variable)
+                }
+                _ => None,
+            },
+            Node::Builtin(Builtin::Sin, argument) => {
+                let (slope, _) = self.linear(argument, variable)?;
+                let cos = self.builtin(Builtin::Cos, argument);
+                let integral = self.div(cos, slope);
+                Some(self.neg(integral))
+            }
+            Node::Builtin(Builtin::Cos, argument) => {
+                let (slope, _) = self.linear(argument, variable)?;
+                let sin = self.builtin(Builtin::Sin, argument);
+                Some(self.div(sin, slope))
+            }
+            _ => None,
+        }
+    }
+
+    /// Antiderivative of `1 / (a + b x + c x^2)` for a positive discriminant
+    /// `d = 4ac - b^2`, as of a Breit-Wigner: `2 / sqrt(d) atan((2cx + b) /
+    /// sqrt(d))`. Returns `None` when `d` is a constant that is not positive.
+    fn reciprocal_quadratic_integral(
+        &mut self,
+        constant: NodeId,
+        slope: NodeId,
+        curvature: NodeId,
+        variable: NodeId,
+    ) -> Option<NodeId> {
+        let four = self.constant(4.0);
+        let two = self.constant(2.0);
+        let product = self.mul(constant, curvature);
+        let product = self.mul(four, product);
+        let square = self.mul(slope, slope);
+        let discriminant = self.sub(product, square);
+        if self
+            .constant_value(discriminant)
+            .is_some_and(|value| value <= 0.0)
+        {
+            return None;
+        }
+        let half = self.constant(0.5);
+        let root = self.powf(discriminant, half);
+
+        let doubled = self.mul(two, curvature);
+        let line = self.mul(doubled, variable);
+        let line = self.add(line, slope);
+        let argument = self.div(line, root);
+        let atan = self.builtin(Builtin::Atan, argument);
+        let scale = self.div(two, root);
+        Some(self.mul(scale, atan))
+    }
+
+    /// Antiderivative of `exp(a + b x + c x^2)` for negative `c`:
+    /// `sqrt(pi) / (2 sqrt(-c)) exp(a - b^2 / 4c) erf(sqrt(-c) (x + b / 2c))`.
+    /// Returns `None` when `c` is a constant that is not negative.
+    fn gaussian_integral(
+        &mut self,
+        constant: NodeId,
+        slope: NodeId,
+        curvature: NodeId,
+        variable: NodeId,
+    ) -> Option<NodeId> {
+        if self
+            .constant_value(curvature)
+            .is_some_and(|value| value >= 0.0)
+        {
+            return None;
+        }
+        let negated = self.neg(curvature);
+        let half = self.constant(0.5);
+        let width = self.powf(negated, half);
+        let two = self.constant(2.0);
+        let four = self.constant(4.0);
+
+        let doubled = self.mul(two, curvature);
+        let shift = self.div(slope, doubled);
+        let shifted = self.add(variable, shift);
+        let argument = self.mul(width, shifted);
+        let erf = self.builtin(Builtin::Erf, argument);
+
+        let square = self.mul(slope, slope);
+        let quadrupled = self.mul(four, curvature);
+        let completed = self.div(square, quadrupled);
+        let exponent = self.sub(constant, completed);
+        let scale = self.builtin(Builtin::Exp, exponent);
+
+        let root_pi = self.constant(Float::PI.sqrt());
+        let denominator = self.mul(two, width);
+        let prefactor = self.div(root_pi, denominator);
+        let prefactor = self.mul(prefactor, scale);
+        Some(self.mul(prefactor, erf))
+    }
+
+    /// `ln|argument|`, the antiderivative of `1 / x` on either side of zero.
+    fn log_abs(&mut self, argument: NodeId) -> NodeId {
+        let magnitude = self.builtin(Builtin::Abs, argument);
+        self.builtin(Builtin::Log, magnitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Checks `F(high) - F(low)` against Simpson's rule for the integrand.
+    fn check(
+        graph: &mut ExpressionGraph,
+        integrand: NodeId,
+        variable: NodeId,
+        values: &[(&str, Float)],
+        (low, high): (Float, Float),
+    ) {
+        let integral = graph.antiderivative(integrand, variable).unwrap();
+        let mut values: HashMap<String, Float> =
+            values.iter().map(|&(n, v)| (n.to_string(), v)).collect();
+        let mut at = |graph: &ExpressionGraph, output: NodeId, x: Float| {
+            values.insert("x".to_string(), x);
+            graph.evaluate(output, &values)
+        };
+        let steps = 2000;
+        let h = (high - low) / steps as Float;
+        let mut numeric = at(graph, integrand, low) + at(graph, integrand, high);
+        for i in 1..steps {
+            let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+            numeric += weight * at(graph, integrand, low + i as Float * h);
+        }
+        numeric *= h / 3.0;
+        let analytic = at(graph, integral, high) - at(graph, integral, low);
+        assert!(
+            (analytic - numeric).abs() < 1e-8 * numeric.abs().max(1.0),
+            "{} != {}",
+            analytic,
+            numeric
+        );
+    }
+
+    fn variables(graph: &mut ExpressionGraph) -> (NodeId, NodeId, NodeId) {
+        let x = graph.insert(Node::new_variable("x".to_string(), true));
+        let a = graph.insert(Node::new_variable("a".to_string(), false));
+        let b = graph.insert(Node::new_variable("b".to_string(), false));
+        (x, a, b)
+    }
+
+    #[test]
+    fn polynomials_and_exponentials() {
+        let mut graph = ExpressionGraph::new();
+        let (x, a, b) = variables(&mut graph);
+        let shifted = graph.sub(x, a);
+        let square = graph.powi(shifted, 2);
+        let line = graph.mul(b, x);
+        let polynomial = graph.add(square, line);
+        check(
+            &mut graph,
+            polynomial,
+            x,
+            &[("a", 0.3), ("b", 2.0)],
+            (-1.0, 2.0),
+        );
+
+        let rate = graph.mul(a, x);
+        let decay = graph.neg(rate);
+        let exponential = graph.builtin(Builtin::Exp, decay);
+        check(&mut graph, exponential, x, &[("a", 1.5)], (0.0, 3.0));
+
+        let cosine = graph.builtin(Builtin::Cos, line);
+        let sum = graph.add(exponential, cosine);
+        check(&mut graph, sum, x, &[("a", 1.5), ("b", 0.7)], (0.0, 3.0));
+    }
+
+    #[test]
+    fn gaussian_through_erf() {
+        let mut graph = ExpressionGraph::new();
+        let (x, mu, sigma) = variables(&mut graph);
+        let shifted = graph.sub(x, mu);
+        let pull = graph.div(shifted, sigma);
+        let square = graph.powi(pull, 2);
+        let two = graph.constant(2.0);
+        let half = graph.div(square, two);
+        let exponent = graph.neg(half);
+        let gaussian = graph.builtin(Builtin::Exp, exponent);
+        check(
+            &mut graph,
+            gaussian,
+            x,
+            &[("a", 0.4), ("b", 1.3)],
+            (-3.0, 2.5),
+        );
+
+        let square = graph.mul(x, x);
+        let growing = graph.builtin(Builtin::Exp, square);
+        assert!(graph.antiderivative(growing, x).is_none());
+    }
+
+    #[test]
+    fn power_laws() {
+        let mut graph = ExpressionGraph::new();
+        let (x, a, b) = variables(&mut graph);
+        let base = graph.add(x, b);
+        let power = graph.powf(base, a);
+        check(&mut graph, power, x, &[("a", -2.5), ("b", 1.0)], (0.5, 4.0));
+
+        let one = graph.constant(1.0);
+        let reciprocal = graph.div(one, base);
+        check(&mut graph, reciprocal, x, &[("b", 1.0)], (0.5, 4.0));
+
+        let inverse_square = graph.powi(base, -2);
+        check(&mut graph, inverse_square, x, &[("b", 1.0)], (0.5, 4.0));
+
+        // Logarithms of bases that are negative over the range.
+        check(&mut graph, reciprocal, x, &[("b", -5.0)], (0.0, 4.0));
+        let inverse = graph.powi(base, -1);
+        check(&mut graph, inverse, x, &[("b", -5.0)], (0.0, 4.0));
+        let minus_one = graph.constant(-1.0);
+        let inverse = graph.powf(base, minus_one);
+        check(&mut graph, inverse, x, &[("b", -5.0)], (0.0, 4.0));
+
+        // A Breit-Wigner, 1 / ((x - a)^2 + b^2 / 4).
+        let shifted = graph.sub(x, a);
+        let square = graph.powi(shifted, 2);
+        let width = graph.mul(b, b);
+        let four = graph.constant(4.0);
+        let width = graph.div(width, four);
+        let denominator = graph.add(square, width);
+        let breit_wigner = graph.div(one, denominator);
+        check(
+            &mut graph,
+            breit_wigner,
+            x,
+            &[("a", 1.0), ("b", 0.6)],
+            (-1.0, 4.0),
+        );
+
+        let product = graph.mul(x, x);
+        let quotient = graph.div(one, product);
+        let ratio = graph.add(quotient, one);
+        assert!(graph.antiderivative(ratio, x).is_none());
+    }
+}
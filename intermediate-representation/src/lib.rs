@@ -1,9 +1,14 @@
 use std::f64;
 
 pub mod binary_operation;
+pub mod branch;
 pub mod builtin;
 pub mod constant;
+pub mod derivative;
 pub mod expression;
+pub mod integral;
+pub mod shapes;
+pub mod special;
 pub mod variable;
 
 #[cfg(feature = "f64")]
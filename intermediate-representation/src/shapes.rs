@@ -0,0 +1,86 @@
+use crate::special::faddeeva_re;
+use crate::{Float, FloatConsts};
+
+/// The source of this module. `#[define_model]` parses it to inline calls to
+/// the shapes below into the expression graph, so that they are differentiated
+/// like the rest of the model. Their bodies are therefore limited to what the parser
+/// supports. Whether or not a shape is normalized, the generated model
+/// normalizes it over the range of its observable.
+pub const SOURCE: &str = include_str!("shapes.rs");
+
+/// Gaussian `exp(-((x - mu) / sigma)^2 / 2)`.
+pub fn gaussian(x: Float, mu: Float, sigma: Float) -> Float {
+    (-((x - mu) / sigma).powi(2) / 2.0).exp()
+}
+
+/// The power-law tail `A (B + s)^-n` of a Crystal Ball function at `s`
+/// standard deviations from the mean, joined to `exp(-s^2 / 2)` at `s =
+/// alpha`.
+fn power_law_tail(s: Float, alpha: Float, n: Float) -> Float {
+    let log_scale = n * (n / alpha).ln() - alpha * alpha / 2.0;
+    (log_scale - n * (n / alpha - alpha + s).ln()).exp()
+}
+
+/// Crystal Ball function: a Gaussian core with a power-law tail of exponent
+/// `n` below `mu - alpha sigma`, for `alpha > 0` and `n > 1`.
+pub fn crystal_ball(x: Float, mu: Float, sigma: Float, alpha: Float, n: Float) -> Float {
+    let t = (x - mu) / sigma;
+    if t > -alpha {
+        (-t * t / 2.0).exp()
+    } else {
+        power_law_tail(-t, alpha, n)
+    }
+}
+
+/// Crystal Ball function with power-law tails on both sides of the Gaussian
+/// core, below `mu - alpha_low sigma` and above `mu + alpha_high sigma`.
+pub fn double_sided_crystal_ball(
+    x: Float,
+    mu: Float,
+    sigma: Float,
+    alpha_low: Float,
+    n_low: Float,
+    alpha_high: Float,
+    n_high: Float,
+) -> Float {
+    let t = (x - mu) / sigma;
+    if t < -alpha_low {
+        power_law_tail(-t, alpha_low, n_low)
+    } else if t > alpha_high {
+        power_law_tail(t, alpha_high, n_high)
+    } else {
+        (-t * t / 2.0).exp()
+    }
+}
+
+/// Non-relativistic Breit-Wigner, `1 / ((x - mean)^2 + width^2 / 4)`.
+pub fn breit_wigner(x: Float, mean: Float, width: Float) -> Float {
+    1.0 / ((x - mean).powi(2) + width * width / 4.0)
+}
+
+/// Voigt profile: a Breit-Wigner of full `width` convolved with a Gaussian of
+/// standard deviation `sigma`, normalized over the real line.
+pub fn voigtian(x: Float, mean: Float, width: Float, sigma: Float) -> Float {
+    let scale = sigma * (2.0 as Float).sqrt();
+    faddeeva_re((x - mean) / scale, width / (2.0 * scale)) / (scale * Float::PI.sqrt())
+}
+
+/// ARGUS background, `x (1 - (x / endpoint)^2)^power exp(curvature (1 - (x /
+/// endpoint)^2))` between zero and the endpoint and zero elsewhere.
+pub fn argus(x: Float, endpoint: Float, curvature: Float, power: Float) -> Float {
+    let u = 1.0 - (x / endpoint).powi(2);
+    if u > 0.0 && x > 0.0 {
+        x * u.powf(power) * (curvature * u).exp()
+    } else {
+        0.0
+    }
+}
+
+/// Johnson's SU distribution, the normal distribution of `gamma + delta
+/// asinh((x - mu) / lambda)`, normalized over the real line.
+pub fn johnson_su(x: Float, mu: Float, lambda: Float, gamma: Float, delta: Float) -> Float {
+    let z = (x - mu) / lambda;
+    let s = gamma + delta * z.asinh();
+    let norm = delta / (lambda * (2.0 * Float::PI).sqrt());
+    norm / (1.0 + z * z).sqrt() * (-s * s / 2.0).exp()
+}
@@ -24,39 +24,1364 @@ mod gaussian {
     }
 }
 
+#[define_model]
+mod extended_gaussian {
+
+    pub struct ExtendedGaussian {
+        pub mu: Parameter,
+        pub sigma: Parameter,
+        #[yield_parameter]
+        pub n: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(mu: Float, sigma: Float, x: Float) -> Float {
+        let norm = (2.0 * Float::PI).powf(-0.5) / sigma;
+        norm * (-((x - mu) / sigma).powi(2) / 2.0).exp()
+    }
+}
+
+#[define_model]
+mod decay {
+
+    pub struct Decay {
+        pub lambda: Parameter,
+        pub t: Data,
+    }
+
+    pub fn distribution(lambda: Float, t: Float) -> Float {
+        (-lambda * t).exp()
+    }
+}
+
+#[define_model]
+mod exponential {
+
+    pub struct Exponential {
+        pub lambda: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(lambda: Float, x: Float) -> Float {
+        (-lambda * x).exp()
+    }
+}
+
+#[define_model]
+mod power_law {
+
+    pub struct PowerLaw {
+        pub k: Parameter,
+        pub c: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(k: Float, c: Float, x: Float) -> Float {
+        x.powf(k) + c * x
+    }
+}
+
+#[define_model]
+mod normalized_shape {
+
+    pub struct NormalizedShape {
+        pub mu: Parameter,
+        pub sigma: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(mu: Float, sigma: Float, x: Float) -> Float {
+        (-((x - mu) / sigma).powi(2) / 2.0).exp()
+    }
+
+    pub fn norm(sigma: Float) -> Float {
+        sigma * (2.0 * Float::PI).powf(0.5)
+    }
+}
+
+#[define_model]
+mod elementary {
+
+    pub struct Elementary {
+        pub a: Parameter,
+        pub b: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(a: Float, b: Float, x: Float) -> Float {
+        let r = (x - a).hypot(b);
+        let angle = Float::atan2(b, x).cosh() + (x * a).sinh().atan();
+        let bounded = (x * b).exp_m1().abs().min(3.0) + r.log10().abs().max(0.2);
+        (angle + b.ln_1p() + bounded + (a * x).tanh()).sqrt() / (1.0 + r)
+    }
+}
+
+#[define_model]
+mod special_terms {
+
+    pub struct SpecialTerms {
+        pub kappa: Parameter,
+        pub mu: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(kappa: Float, mu: Float, x: Float) -> Float {
+        let von_mises = (kappa * (x - mu).cos()).exp() / (2.0 * Float::PI * bessel_i0(kappa));
+        let tail = erfc(mu * x) * gamma_q(2.5, kappa * x * x) + bessel_i1(kappa) + erf(mu);
+        von_mises * tail * (ln_gamma(kappa + 1.0) - digamma(kappa)).exp()
+    }
+}
+
+#[define_model]
+mod piecewise {
+
+    pub struct Piecewise {
+        pub a: Parameter,
+        pub b: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(a: Float, b: Float, x: Float) -> Float {
+        let core = if x > a {
+            (x - a).sqrt()
+        } else if x < a - 1.0 {
+            b * (a - x).ln()
+        } else {
+            b * (a - x)
+        };
+        let scale = match x {
+            v if v < -2.0 => b * b,
+            0.0..=1.0 | 3.0 => a.exp(),
+            v if v > 2.5 && !(v > 4.0 || v == 2.7) => (v - a).abs(),
+            _ => 1.0 + b,
+        };
+        (1.0 + core * core) * scale
+    }
+}
+
+#[define_model]
+mod inlined {
+
+    pub struct Inlined {
+        pub mu: Parameter,
+        pub sigma: Parameter,
+        pub x: Data,
+    }
+
+    fn pull(x: Float, mu: Float, sigma: Float) -> Float {
+        (x - mu) / sigma
+    }
+
+    fn gaussian(t: Float) -> Float {
+        let t = t * t;
+        (-t / 2.0).exp()
+    }
+
+    fn shape(x: Float, mu: Float, sigma: Float) -> Float {
+        gaussian(pull(x, mu, sigma))
+    }
+
+    pub fn distribution(mu: Float, sigma: Float, x: Float) -> Float {
+        let scale = {
+            let half: Float = 0.5;
+            half * 2.0
+        };
+        let x = x * scale;
+        shape(x, mu, sigma) * scale
+    }
+}
+
+#[define_model]
+mod gaussian_peak {
+
+    pub struct GaussianPeak {
+        pub mu: Parameter,
+        pub sigma: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(mu: Float, sigma: Float, x: Float) -> Float {
+        gaussian(x, mu, sigma)
+    }
+}
+
+#[define_model]
+mod crystal_ball_peak {
+
+    pub struct CrystalBallPeak {
+        pub mu: Parameter,
+        pub sigma: Parameter,
+        pub alpha: Parameter,
+        pub n: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(mu: Float, sigma: Float, alpha: Float, n: Float, x: Float) -> Float {
+        crystal_ball(x, mu, sigma, alpha, n)
+    }
+}
+
+#[define_model]
+mod double_sided_peak {
+
+    pub struct DoubleSidedPeak {
+        pub mu: Parameter,
+        pub sigma: Parameter,
+        pub alpha_low: Parameter,
+        pub n_low: Parameter,
+        pub alpha_high: Parameter,
+        pub n_high: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(
+        mu: Float,
+        sigma: Float,
+        alpha_low: Float,
+        n_low: Float,
+        alpha_high: Float,
+        n_high: Float,
+        x: Float,
+    ) -> Float {
+        double_sided_crystal_ball(x, mu, sigma, alpha_low, n_low, alpha_high, n_high)
+    }
+}
+
+#[define_model]
+mod breit_wigner_peak {
+
+    pub struct BreitWignerPeak {
+        pub mean: Parameter,
+        pub width: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(mean: Float, width: Float, x: Float) -> Float {
+        breit_wigner(x, mean, width)
+    }
+}
+
+#[define_model]
+mod voigtian_peak {
+
+    pub struct VoigtianPeak {
+        pub mean: Parameter,
+        pub width: Parameter,
+        pub sigma: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(mean: Float, width: Float, sigma: Float, x: Float) -> Float {
+        voigtian(x, mean, width, sigma)
+    }
+}
+
+#[define_model]
+mod argus_background {
+
+    pub struct ArgusBackground {
+        pub endpoint: Parameter,
+        pub curvature: Parameter,
+        pub power: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(endpoint: Float, curvature: Float, power: Float, x: Float) -> Float {
+        argus(x, endpoint, curvature, power)
+    }
+}
+
+#[define_model]
+mod johnson_background {
+
+    pub struct JohnsonBackground {
+        pub mu: Parameter,
+        pub lambda: Parameter,
+        pub gamma: Parameter,
+        pub delta: Parameter,
+        pub x: Data,
+    }
+
+    pub fn distribution(mu: Float, lambda: Float, gamma: Float, delta: Float, x: Float) -> Float {
+        johnson_su(x, mu, lambda, gamma, delta)
+    }
+}
+
+#[define_model]
+mod chebyshev_background {
+
+    pub struct ChebyshevBackground {
+        pub c1: Parameter,
+        pub c2: Parameter,
+        pub c3: Parameter,
+        pub x: Data,
+    }
+
+    /// Over `[0, 10]`, mapped onto `[-1, 1]`.
+    pub fn distribution(c1: Float, c2: Float, c3: Float, x: Float) -> Float {
+        let u = x / 5.0 - 1.0;
+        1.0 + c1 * chebyshev(1, u) + c2 * chebyshev(2, u) + c3 * chebyshev(3, u)
+    }
+}
+
+#[define_model]
+mod bernstein_background {
+
+    pub struct BernsteinBackground {
+        pub b0: Parameter,
+        pub b1: Parameter,
+        pub b2: Parameter,
+        pub b3: Parameter,
+        pub x: Data,
+    }
+
+    /// Over `[1, 3]`, mapped onto `[0, 1]`.
+    pub fn distribution(b0: Float, b1: Float, b2: Float, b3: Float, x: Float) -> Float {
+        let u = (x - 1.0) / 2.0;
+        b0 * bernstein(0, 3, u)
+            + b1 * bernstein(1, 3, u)
+            + b2 * bernstein(2, 3, u)
+            + b3 * bernstein(3, 3, u)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rand::Rng;
-    #[allow(unused_imports)]
-    use rayon::prelude::*;
-    use std::time;
+    use fitting::binned::{BinEvaluation, BinnedNegativeLogLikelihood, ChiSquare, ChiSquareKind};
+    use fitting::composite::{Coefficients, Convolution, ProductModel, SumModel};
+    use fitting::data::{Dataset, Distribution};
+    use fitting::generation::{Generation, Generator, Method};
+    use fitting::histogram::Histogram;
+    use fitting::integration;
+    use fitting::likelihood::{NegativeLogLikelihood, WeightedErrors};
+    use fitting::minimize::{FitStatus, Hesse, Migrad, Minimizer};
+    use fitting::model::Model;
+    use fitting::simultaneous::SimultaneousLikelihood;
+    use intermediate_representation::shapes::{argus, crystal_ball, johnson_su, voigtian};
+    use intermediate_representation::special::{erfc, normal_cdf};
+    use intermediate_representation::{Float, FloatConsts};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Seeded from the arguments, so that a failure reproduces while distinct
+    /// samples within a test stay independent.
+    fn gaussian_sample(n: usize, mu: Float, sigma: Float) -> Dataset {
+        let seed = n as u64 ^ mu.to_bits() ^ sigma.to_bits().rotate_left(17);
+        gaussian_sample_with(&mut StdRng::seed_from_u64(seed), n, mu, sigma)
+    }
+
+    fn gaussian_sample_with(rng: &mut impl Rng, n: usize, mu: Float, sigma: Float) -> Dataset {
+        let mut dataset = Dataset::new(vec!["x".to_string()]);
+        for _ in 0..n {
+            let u: Float = rng.random();
+            let v: Float = rng.random();
+            let z = (-2.0 * (1.0 - u).ln()).sqrt() * (2.0 * Float::PI * v).cos();
+            dataset.push(&[mu + sigma * z]);
+        }
+        dataset
+    }
+
+    type Gradient<const P: usize> = fn([Float; P], [Float; 1]) -> (Float, [Float; P]);
+    type Hessian<const P: usize> =
+        fn([Float; P], [Float; 1]) -> (Float, [Float; P], [[Float; P]; P]);
+
+    /// Checks the generated functions of a one-dimensional model against `f`
+    /// at each of `xs`: the value exactly, the gradients against central
+    /// differences of `f` and the Hessian against forward differences of the
+    /// likelihood gradient.
+    fn check_generated<const P: usize>(
+        f: impl Fn([Float; P], Float) -> Float,
+        value_and_gradient: Gradient<P>,
+        likelihood: Gradient<P>,
+        likelihood_hessian: Hessian<P>,
+        parameters: [Float; P],
+        xs: &[Float],
+    ) {
+        for &x in xs {
+            let (value, gradient) = value_and_gradient(parameters, [x]);
+            let (log_value, likelihood_gradient, hessian) = likelihood_hessian(parameters, [x]);
+            let (expected_log_value, expected_gradient) = likelihood(parameters, [x]);
+            assert!((value - f(parameters, x)).abs() < 1e-12, "{}", x);
+            assert!((log_value - expected_log_value).abs() < 1e-12, "{}", x);
+            for i in 0..P {
+                assert!((likelihood_gradient[i] - expected_gradient[i]).abs() < 1e-12);
+
+                let step = 1e-6;
+                let mut shifted = parameters;
+                shifted[i] += step;
+                let forward = f(shifted, x);
+                shifted[i] -= 2.0 * step;
+                let backward = f(shifted, x);
+                let numerical = (forward - backward) / (2.0 * step);
+                assert!(
+                    (gradient[i] - numerical).abs() < 1e-6,
+                    "{} {:?}",
+                    x,
+                    gradient
+                );
+                assert!(
+                    (likelihood_gradient[i] - numerical / value).abs() < 1e-6,
+                    "{} {:?}",
+                    x,
+                    likelihood_gradient
+                );
+
+                let mut shifted = parameters;
+                shifted[i] += step;
+                let (_, shifted_gradient) = likelihood(shifted, [x]);
+                for j in 0..P {
+                    let numerical = (shifted_gradient[j] - likelihood_gradient[j]) / step;
+                    assert!(
+                        (hessian[i][j] - numerical).abs() < 1e-4 * numerical.abs().max(1.0),
+                        "{} {:?}",
+                        x,
+                        hessian
+                    );
+                }
+            }
+        }
+    }
+
+    fn gaussian_model(mu: Float, sigma: Float) -> gaussian::Gaussian {
+        gaussian::Gaussian {
+            mu: Parameter::new("mu".to_string(), false).with_value(mu),
+            sigma: Parameter::new("sigma".to_string(), false)
+                .with_value(sigma)
+                .with_lower_limit(0.0),
+            x: Data::new("x", Distribution::Uniform(-10.0, 10.0)),
+        }
+    }
+
     #[test]
     fn it_works() {
-        // let b = Gaussian::_value_and_gradient([1.0f64, 1.2f64], [0.0f64]);
-        // println!("{:?}", b);
-        // println!("DONE");
-        // assert_eq!(1.0, b.0);
-        let n = 1000000;
-        let mut rng = rand::rng();
-        let b = time::Instant::now();
-        let mut c = 0.0;
-        let mut parameters = [1.0, 0.0];
-        for _ in 0..1 {
-            let vec: Vec<[Float; 1]> = (0..n).map(|_| rng.random()).collect();
-            for i in 0..500 {
-                let k: Float = vec
-                    .par_iter()
-                    .map(|f| gaussian::_likelihood(parameters, *f).0)
-                    .sum();
-                c += k;
-                // c += k.sin();
-                // parameters[0] += k.cos() / 10000.0;
+        let data = gaussian_sample(20000, 0.5, 1.5);
+        let model = gaussian_model(0.0, 1.0);
+        let nll = NegativeLogLikelihood::new(&model, &data);
+        let result = Migrad::new().minimize(&nll, &model.parameter_values());
+
+        assert_eq!(result.status, FitStatus::Converged);
+        let (mu, sigma) = (result.values[0], result.values[1]);
+        assert!((mu - 0.5).abs() < 0.05, "{:?}", result);
+        assert!((sigma - 1.5).abs() < 0.05, "{:?}", result);
+    }
+
+    #[test]
+    fn layout_follows_declaration_order() {
+        let model = gaussian_model(0.0, 1.0);
+        assert_eq!(gaussian::Gaussian::PARAMETERS, ["mu", "sigma"]);
+        assert_eq!(model.parameter_names(), vec!["mu", "sigma"]);
+        assert_eq!(model.data_names(), vec!["x"]);
+        assert_eq!(model.parameter_index("sigma"), Some(1));
+        assert_eq!(model.data_index("x"), Some(0));
+        assert_eq!(model.parameter_index("x"), None);
+
+        let x: Float = 0.7;
+        let expected = (-x * x / 2.0).exp() / (2.0 * Float::PI).sqrt();
+        assert!((model.evaluate(&[x]) - expected).abs() < 1e-12);
+        assert!((gaussian::_likelihood([0.0, 1.0], [x]).0 - expected.ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn hessian_matches_gradient_differences() {
+        let parameters = [0.2, 1.3];
+        let data = [0.7];
+        let (value, gradient, hessian) = gaussian::_likelihood_hessian(parameters, data);
+        let (expected_value, expected_gradient) = gaussian::_likelihood(parameters, data);
+        assert!((value - expected_value).abs() < 1e-12);
+        for i in 0..2 {
+            assert!((gradient[i] - expected_gradient[i]).abs() < 1e-12);
+            let step = 1e-6;
+            let mut shifted = parameters;
+            shifted[i] += step;
+            let (_, shifted_gradient) = gaussian::_likelihood(shifted, data);
+            for j in 0..2 {
+                let numerical = (shifted_gradient[j] - expected_gradient[j]) / step;
+                assert!((hessian[i][j] - numerical).abs() < 1e-4, "{:?}", hessian);
             }
         }
-        println!("Done: {} {:?}", c, b.elapsed());
-        println!("Done: {}", c);
-        println!("{:?}", gaussian::_likelihood([1.0, 0.0], [0.3]));
-        assert_eq!(0.0, 1.0);
+    }
+
+    #[test]
+    fn parameter_exponent() {
+        check_generated(
+            |[k, c], x| x.powf(k) + c * x,
+            power_law::_value_and_gradient,
+            power_law::_likelihood,
+            power_law::_likelihood_hessian,
+            [1.5, 0.4],
+            &[2.0, 0.7],
+        );
+    }
+
+    #[test]
+    fn hesse_errors() {
+        let n = 20000;
+        let data = gaussian_sample(n, 0.5, 1.5);
+        let model = gaussian_model(0.0, 1.0);
+        let nll = NegativeLogLikelihood::new(&model, &data);
+        let mut result = Migrad::new().minimize(&nll, &model.parameter_values());
+        assert!(Hesse::new().run(&nll, &mut result));
+
+        let errors = result.errors().unwrap();
+        let sigma = result.values[1];
+        let expected = [
+            sigma / (n as Float).sqrt(),
+            sigma / (2.0 * n as Float).sqrt(),
+        ];
+        for i in 0..2 {
+            assert!((errors[i] / expected[i] - 1.0).abs() < 0.05, "{:?}", errors);
+        }
+        let correlation = result.correlation().unwrap();
+        assert!((correlation[(0, 0)] - 1.0).abs() < 1e-12);
+        assert!(correlation[(0, 1)].abs() < 0.05);
+    }
+
+    #[test]
+    fn bounded_fit() {
+        let n = 5000;
+        let data = gaussian_sample(n, -0.3, 0.8);
+        let mut model = gaussian_model(0.0, 0.05);
+        model.mu = model.mu.with_limits(-1.0, 1.0);
+        let nll = NegativeLogLikelihood::new(&model, &data);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+
+        assert_eq!(result.status, FitStatus::Converged);
+        assert!((result.values[0] + 0.3).abs() < 0.05, "{:?}", result);
+        assert!((result.values[1] - 0.8).abs() < 0.05, "{:?}", result);
+        let errors = result.errors().unwrap();
+        assert!((errors[0] / (result.values[1] / (n as Float).sqrt()) - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn fixed_mean() {
+        let data = gaussian_sample(5000, 0.4, 1.2);
+        let model = gaussian_model(0.4, 1.0);
+        let nll = NegativeLogLikelihood::new(&model, &data);
+        let mut minimizer = Minimizer::new(&nll, nll.parameters());
+        minimizer.fix("mu");
+        let result = minimizer.fit();
+
+        assert_eq!(result.values[0], 0.4);
+        assert_eq!(result.floating, vec![1]);
+        assert!((result.values[1] - 1.2).abs() < 0.05, "{:?}", result);
+    }
+
+    #[test]
+    fn extended_fit() {
+        let data = gaussian_sample(3000, 0.0, 1.0);
+        let model = extended_gaussian::ExtendedGaussian {
+            mu: Parameter::new("mu".to_string(), false),
+            sigma: Parameter::new("sigma".to_string(), false)
+                .with_value(1.2)
+                .with_lower_limit(0.0),
+            n: Parameter::new("n".to_string(), false)
+                .with_value(2000.0)
+                .with_error(100.0)
+                .with_lower_limit(0.0),
+            x: Data::new("x", Distribution::Uniform(-10.0, 10.0)),
+        };
+        assert_eq!(model.yield_index(), Some(2));
+        let nll = NegativeLogLikelihood::new(&model, &data);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+
+        assert_eq!(result.status, FitStatus::Converged);
+        assert!((result.values[2] - 3000.0).abs() < 1.0, "{:?}", result);
+        let errors = result.errors().unwrap();
+        assert!(
+            (errors[2] - (3000.0 as Float).sqrt()).abs() < 0.5,
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn binned_fits() {
+        let data = gaussian_sample(20000, 0.5, 1.5);
+        let histogram = Histogram::from_dataset(
+            &data,
+            "x",
+            (0..=60).map(|i| -10.0 + i as Float / 3.0).collect(),
+        );
+        let model = gaussian_model(0.0, 1.0);
+
+        for evaluation in [BinEvaluation::Centre, BinEvaluation::Integrated] {
+            let nll =
+                BinnedNegativeLogLikelihood::new(&model, &histogram).with_evaluation(evaluation);
+            let result = Minimizer::new(&nll, nll.parameters()).fit();
+            assert_eq!(result.status, FitStatus::Converged);
+            assert!((result.values[0] - 0.5).abs() < 0.05, "{:?}", result);
+            assert!((result.values[1] - 1.5).abs() < 0.05, "{:?}", result);
+            let errors = result.errors().unwrap();
+            assert!((errors[0] / (1.5 / (20000.0 as Float).sqrt()) - 1.0).abs() < 0.1);
+        }
+
+        for kind in [ChiSquareKind::Neyman, ChiSquareKind::Pearson] {
+            let chi_square = ChiSquare::new(&model, &histogram, kind);
+            let result = Minimizer::new(&chi_square, chi_square.parameters()).fit();
+            assert_eq!(result.status, FitStatus::Converged);
+            assert!((result.values[0] - 0.5).abs() < 0.05, "{:?}", result);
+            assert!((result.values[1] - 1.5).abs() < 0.1, "{:?}", result);
+        }
+    }
+
+    #[test]
+    fn weighted_binned_fit() {
+        let n = 20000;
+        let sample = gaussian_sample(n, 0.2, 1.0);
+        let mut data = Dataset::new(vec!["x".to_string()]);
+        for event in sample.events() {
+            data.push_weighted(event, 0.25);
+        }
+        let histogram = Histogram::from_dataset(
+            &data,
+            "x",
+            (0..=60).map(|i| -10.0 + i as Float / 3.0).collect(),
+        );
+        assert!(histogram.is_weighted());
+        let model = gaussian_model(0.0, 1.5);
+        let expected = 1.0 / (n as Float).sqrt();
+
+        let nll = BinnedNegativeLogLikelihood::new(&model, &histogram);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        assert!((result.values[0] - 0.2).abs() < 0.05, "{:?}", result);
+        let errors = result.errors().unwrap();
+        assert!((errors[0] / expected - 1.0).abs() < 0.1, "{:?}", errors);
+
+        let chi_square = ChiSquare::new(&model, &histogram, ChiSquareKind::Neyman);
+        let result = Minimizer::new(&chi_square, chi_square.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        assert!((result.values[0] - 0.2).abs() < 0.05, "{:?}", result);
+        let errors = result.errors().unwrap();
+        assert!((errors[0] / expected - 1.0).abs() < 0.1, "{:?}", errors);
+    }
+
+    #[test]
+    fn binned_sub_range_fit() {
+        // Only part of the Gaussian's range is histogrammed, so the shape is
+        // normalized over the histogram rather than the model's range.
+        let data = gaussian_sample(20000, 0.5, 1.5);
+        let histogram = Histogram::from_dataset(
+            &data,
+            "x",
+            (0..=30).map(|i| -1.0 + i as Float / 10.0).collect(),
+        );
+        let model = gaussian_model(0.0, 1.0);
+        let nll = BinnedNegativeLogLikelihood::new(&model, &histogram);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        let errors = result.errors().unwrap();
+        for (value, (expected, error)) in result.values.iter().zip([0.5, 1.5].iter().zip(errors)) {
+            assert!((value - expected).abs() < 3.0 * error, "{:?}", result);
+        }
+        assert!(result.minimum < 60.0, "{:?}", result);
+    }
+
+    #[test]
+    fn weighted_fit() {
+        let n = 5000;
+        let sample = gaussian_sample(n, 0.2, 1.0);
+        let mut data = Dataset::new(vec!["x".to_string(), "w".to_string()]);
+        for event in sample.events() {
+            data.push(&[event[0], 0.25]);
+        }
+        let data = data.with_weight_column("w");
+        assert_eq!(data.columns(), &["x".to_string()]);
+        assert!((data.sum_of_weights() - 0.25 * n as Float).abs() < 1e-9);
+
+        let model = gaussian_model(0.0, 1.5);
+        let expected = 1.0 / (n as Float).sqrt();
+        for weighted_errors in [
+            WeightedErrors::Asymptotic,
+            WeightedErrors::SumOfSquaredWeights,
+        ] {
+            let nll =
+                NegativeLogLikelihood::new(&model, &data).with_weighted_errors(weighted_errors);
+            let result = Minimizer::new(&nll, nll.parameters()).fit();
+            assert_eq!(result.status, FitStatus::Converged);
+            assert!((result.values[0] - 0.2).abs() < 0.05, "{:?}", result);
+            let errors = result.errors().unwrap();
+            assert!((errors[0] / expected - 1.0).abs() < 0.05, "{:?}", errors);
+        }
+    }
+
+    #[test]
+    fn simultaneous_fit() {
+        let narrow = gaussian_sample(4000, 0.3, 0.5);
+        let wide = gaussian_sample(4000, 0.3, 2.0);
+        let mut narrow_model = gaussian_model(0.0, 1.0);
+        narrow_model.sigma = Parameter::new("sigma_narrow".to_string(), false)
+            .with_value(1.0)
+            .with_lower_limit(0.0);
+        let mut wide_model = gaussian_model(0.0, 1.0);
+        wide_model.sigma = Parameter::new("sigma_wide".to_string(), false)
+            .with_value(1.0)
+            .with_lower_limit(0.0);
+
+        let nll = SimultaneousLikelihood::new()
+            .with_category("narrow", &narrow_model, &narrow)
+            .with_category("wide", &wide_model, &wide);
+        let names: Vec<String> = nll
+            .parameters()
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect();
+        assert_eq!(names, ["mu", "sigma_narrow", "sigma_wide"]);
+        assert_eq!(nll.category_parameters("wide"), Some(&[0, 2][..]));
+
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        assert!((result.values[0] - 0.3).abs() < 0.03, "{:?}", result);
+        assert!((result.values[1] - 0.5).abs() < 0.03, "{:?}", result);
+        assert!((result.values[2] - 2.0).abs() < 0.1, "{:?}", result);
+        // The shared mean is dominated by the narrow sample.
+        let expected = 1.0 / (4000.0 / 0.25 + 4000.0 / 4.0 as Float).sqrt();
+        let errors = result.errors().unwrap();
+        assert!((errors[0] / expected - 1.0).abs() < 0.05, "{:?}", errors);
+    }
+
+    #[test]
+    fn toy_study() {
+        let model = gaussian_model(0.0, 1.0);
+        let results = Generation::new(200)
+            .with_seed(7)
+            .run(&model, &[1.0, 2.0], |rng| {
+                gaussian_sample_with(rng, 500, 1.0, 2.0)
+            });
+
+        assert_eq!(results.fits.len(), 200);
+        assert!(results.success_rate() > 0.99);
+        for parameter in ["mu", "sigma"] {
+            let pulls = results.pull_summary(parameter);
+            assert!(pulls.mean.abs() < 3.0 * pulls.mean_error, "{:?}", pulls);
+            assert!(
+                (pulls.width - 1.0).abs() < 3.0 * pulls.width_error,
+                "{:?}",
+                pulls
+            );
+        }
+        let coverage = results.coverage("mu");
+        assert!((coverage - 0.683).abs() < 0.1, "{}", coverage);
+
+        let again = Generation::new(3)
+            .with_seed(7)
+            .run(&model, &[1.0, 2.0], |rng| {
+                gaussian_sample_with(rng, 500, 1.0, 2.0)
+            });
+        assert_eq!(again.fits[2].values, results.fits[2].values);
+    }
+
+    #[test]
+    fn event_generation() {
+        let mut model = gaussian_model(1.0, 2.0);
+        model.x = Data::new("x", Distribution::Uniform(-5.0, 7.0));
+        for method in [Method::InverseCdf, Method::AcceptReject] {
+            let generator = Generator::from_model(&model).with_method(method);
+            let data = generator.generate_seeded(3, 20000);
+            assert_eq!(data.len(), 20000);
+            assert_eq!(data, generator.generate_seeded(3, 20000));
+
+            let values: Vec<Float> = data.events().map(|e| e[0]).collect();
+            assert!(values.iter().all(|x| (-5.0..=7.0).contains(x)));
+            let mean = values.iter().sum::<Float>() / 20000.0;
+            let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<Float>() / 20000.0;
+            assert!((mean - 1.0).abs() < 0.05, "{:?} {}", method, mean);
+            assert!(
+                (variance.sqrt() - 2.0).abs() < 0.05,
+                "{:?} {}",
+                method,
+                variance
+            );
+        }
+
+        let generator = Generator::new(&model, &[0.0, 1.0], &[(-10.0, 10.0)]);
+        let results =
+            Generation::new(50).run(&model, &[0.0, 1.0], |rng| generator.generate(rng, 1000));
+        assert!(results.success_rate() > 0.95);
+    }
+
+    #[test]
+    fn numeric_normalization() {
+        let model = decay::Decay {
+            lambda: Parameter::new("lambda".to_string(), false)
+                .with_value(0.5)
+                .with_lower_limit(0.0),
+            t: Data::new("t", Distribution::Uniform(0.0, 5.0)),
+        };
+        let lambda: Float = 0.8;
+        let mut gradient = [0.0];
+        let normalization = model.log_normalization(&[lambda], &mut gradient);
+        let integral = (1.0 - (-5.0 * lambda).exp()) / lambda;
+        let derivative = 5.0 * (-5.0 * lambda).exp() / lambda - integral / lambda;
+        assert!((normalization - integral.ln()).abs() < 1e-10);
+        assert!((gradient[0] - derivative / integral).abs() < 1e-8);
+
+        let data = Generator::new(&model, &[lambda], &[(0.0, 5.0)]).generate_seeded(5, 20000);
+        let nll = NegativeLogLikelihood::new(&model, &data);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        let errors = result.errors().unwrap();
+        assert!(
+            (result.values[0] - lambda).abs() < 3.0 * errors[0],
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn analytic_normalization() {
+        let mut model = gaussian_model(0.0, 1.0);
+        model.x = Data::new("x", Distribution::Uniform(-1.0, 2.5));
+        let parameters = [0.3, 1.7];
+        let mut gradient = [0.0; 2];
+        let normalization = model.log_normalization(&parameters, &mut gradient);
+        let probability = normal_cdf((2.5 - 0.3) / 1.7) - normal_cdf((-1.0 - 0.3) / 1.7);
+        assert!((normalization - probability.ln()).abs() < 1e-10);
+
+        let mut numeric_gradient = [0.0; 2];
+        let numeric = integration::log_integral(
+            integration::integrate_box(
+                &[(-1.0, 2.5)],
+                2,
+                integration::TOLERANCE,
+                &mut |event, g| model.distribution(&parameters, event, g),
+            ),
+            &mut numeric_gradient,
+        );
+        assert!((normalization - numeric).abs() < 1e-10);
+        for (analytic, numeric) in gradient.iter().zip(numeric_gradient) {
+            assert!((analytic - numeric).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn sum_fit() {
+        let mut signal = gaussian_model(5.0, 0.5);
+        signal.x = Data::new("x", Distribution::Uniform(0.0, 10.0));
+        let background = exponential::Exponential {
+            lambda: Parameter::new("lambda".to_string(), false)
+                .with_value(0.5)
+                .with_lower_limit(0.0),
+            x: Data::new("x", Distribution::Uniform(0.0, 10.0)),
+        };
+        let fraction = Parameter::new("f".to_string(), false)
+            .with_value(0.5)
+            .with_limits(0.0, 1.0);
+        let model = SumModel::new(Coefficients::Fractions)
+            .with_component(&signal, fraction)
+            .with_remainder(&background);
+        assert_eq!(model.parameter_names(), ["mu", "sigma", "f", "lambda"]);
+
+        let truth = [5.0, 0.5, 0.3, 0.3];
+        let event = [4.2];
+        let mut gradient = [0.0; 4];
+        model.log_likelihood(&truth, &event, &mut gradient);
+        let log_likelihood =
+            |parameters: &[Float]| model.log_likelihood(parameters, &event, &mut [0.0; 4]);
+        for (i, derivative) in gradient.iter().enumerate() {
+            let step = 1e-6;
+            let mut shifted = truth;
+            shifted[i] += step;
+            let forward = log_likelihood(&shifted);
+            shifted[i] -= 2.0 * step;
+            let backward = log_likelihood(&shifted);
+            let numeric = (forward - backward) / (2.0 * step);
+            assert!(
+                (derivative - numeric).abs() < 1e-6,
+                "{} {}",
+                derivative,
+                numeric
+            );
+        }
+
+        let data = Generator::new(&model, &truth, &[(0.0, 10.0)]).generate_seeded(11, 10000);
+        let nll = NegativeLogLikelihood::new(&model, &data);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        let errors = result.errors().unwrap();
+        for i in 0..4 {
+            assert!(
+                (result.values[i] - truth[i]).abs() < 3.0 * errors[i],
+                "{:?}",
+                result
+            );
+        }
+
+        let yields = SumModel::new(Coefficients::Yields)
+            .with_component(
+                &signal,
+                Parameter::new("signal".to_string(), false)
+                    .with_value(5000.0)
+                    .with_error(100.0),
+            )
+            .with_component(
+                &background,
+                Parameter::new("background".to_string(), false)
+                    .with_value(5000.0)
+                    .with_error(100.0),
+            );
+        let nll = NegativeLogLikelihood::new(&yields, &data);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        let errors = result.errors().unwrap();
+        assert!((result.values[2] + result.values[4] - 10000.0).abs() < 1.0);
+        assert!(
+            (result.values[2] - 3000.0).abs() < 3.0 * errors[2],
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn product_fit() {
+        let mut mass = gaussian_model(1.0, 0.5);
+        mass.x = Data::new("x", Distribution::Uniform(-2.0, 4.0));
+        let time = decay::Decay {
+            lambda: Parameter::new("lambda".to_string(), false)
+                .with_value(0.5)
+                .with_lower_limit(0.0),
+            t: Data::new("t", Distribution::Uniform(0.0, 5.0)),
+        };
+        let model = ProductModel::new()
+            .with_mapped_component(&mass, &["mass"])
+            .with_component(&time);
+        assert_eq!(model.parameter_names(), ["mu", "sigma", "lambda"]);
+        assert_eq!(model.data_names(), ["mass", "t"]);
+        assert_eq!(model.component_parameters(1), [2]);
+
+        let truth = [1.0, 0.5, 0.8];
+        let mut gradient = [0.0; 3];
+        let mut mass_gradient = [0.0; 2];
+        let mut time_gradient = [0.0];
+        let normalization = model.log_normalization(&truth, &mut gradient);
+        let expected = mass.log_normalization(&truth[..2], &mut mass_gradient)
+            + time.log_normalization(&truth[2..], &mut time_gradient);
+        assert!((normalization - expected).abs() < 1e-12);
+        assert_eq!(gradient[2], time_gradient[0]);
+
+        let data =
+            Generator::new(&model, &truth, &[(-2.0, 4.0), (0.0, 5.0)]).generate_seeded(13, 10000);
+        let nll = NegativeLogLikelihood::new(&model, &data);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        let errors = result.errors().unwrap();
+        for i in 0..3 {
+            assert!(
+                (result.values[i] - truth[i]).abs() < 3.0 * errors[i],
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn convolution_fit() {
+        let physics = decay::Decay {
+            lambda: Parameter::new("lambda".to_string(), false)
+                .with_value(0.5)
+                .with_lower_limit(0.0),
+            t: Data::new("t", Distribution::Uniform(0.0, 10.0)),
+        };
+        let resolution = gaussian::Gaussian {
+            mu: Parameter::new("bias".to_string(), true),
+            sigma: Parameter::new("sigma".to_string(), false)
+                .with_value(0.5)
+                .with_lower_limit(0.0),
+            x: Data::new("residual", Distribution::Uniform(-3.0, 3.0)),
+        };
+        let model = Convolution::new(&physics, &resolution)
+            .with_observable(Data::new("t", Distribution::Uniform(-1.0, 10.0)));
+        assert_eq!(model.parameter_names(), ["lambda", "bias", "sigma"]);
+
+        // Exponential of rate l convolved with a Gaussian of width s, far from
+        // the ends of the ranges.
+        let (lambda, sigma): (Float, Float) = (0.8, 0.3);
+        let truth = [lambda, 0.0, sigma];
+        for t in [0.1, 1.5, 3.0] {
+            let mut gradient = [0.0; 3];
+            let value = model.distribution(&truth, &[t], &mut gradient);
+            let expected = 0.5
+                * (0.5 * lambda * (lambda * sigma * sigma - 2.0 * t)).exp()
+                * erfc((lambda * sigma * sigma - t) / (2.0 as Float).sqrt() / sigma);
+            assert!((value - expected).abs() < 1e-9, "{} {}", value, expected);
+
+            for (i, derivative) in gradient.iter().enumerate() {
+                let step = 1e-6;
+                let mut shifted = truth;
+                shifted[i] += step;
+                let forward = model.distribution(&shifted, &[t], &mut [0.0; 3]);
+                shifted[i] -= 2.0 * step;
+                let backward = model.distribution(&shifted, &[t], &mut [0.0; 3]);
+                let numeric = (forward - backward) / (2.0 * step);
+                assert!(
+                    (derivative - numeric).abs() < 1e-6,
+                    "{} {}",
+                    derivative,
+                    numeric
+                );
+            }
+        }
+
+        // The lower edge of the observable is reachable through the resolution,
+        // while at -3 the residual range only touches the physics range.
+        let mut gradient = [0.0; 3];
+        assert!(
+            model
+                .log_likelihood(&truth, &[-1.0], &mut gradient)
+                .is_finite()
+        );
+        assert!(gradient.iter().all(|g| g.is_finite()));
+        let value = model.log_likelihood(&truth, &[-3.0], &mut gradient);
+        assert_eq!(value, Float::NEG_INFINITY);
+        assert_eq!(gradient, [0.0; 3]);
+
+        let data = Generator::new(&model, &truth, &[(-1.0, 10.0)]).generate_seeded(17, 2000);
+        let nll = NegativeLogLikelihood::new(&model, &data);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        let errors = result.errors().unwrap();
+        for i in [0, 2] {
+            assert!(
+                (result.values[i] - truth[i]).abs() < 3.0 * errors[i],
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn user_normalization() {
+        let shape = normalized_shape::NormalizedShape {
+            mu: Parameter::new("mu".to_string(), false),
+            sigma: Parameter::new("sigma".to_string(), false)
+                .with_value(1.0)
+                .with_lower_limit(0.0),
+            x: Data::new("x", Distribution::Uniform(-10.0, 10.0)),
+        };
+        let reference = gaussian_model(0.0, 1.0);
+        let parameters = [0.3, 1.7];
+        let event = [1.1];
+        let close = |a: &[Float], b: &[Float]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-12);
+
+        let (mut g, mut reference_g) = ([0.0; 2], [0.0; 2]);
+        let value = shape.distribution(&parameters, &event, &mut g);
+        let reference_value = reference.distribution(&parameters, &event, &mut reference_g);
+        assert!(close(&[value], &[reference_value]) && close(&g, &reference_g));
+
+        let value = shape.log_likelihood(&parameters, &event, &mut g);
+        let reference_value = reference.log_likelihood(&parameters, &event, &mut reference_g);
+        assert!(close(&[value], &[reference_value]) && close(&g, &reference_g));
+
+        let (mut h, mut reference_h) = ([0.0; 4], [0.0; 4]);
+        shape.log_likelihood_hessian(&parameters, &event, &mut g, &mut h);
+        reference.log_likelihood_hessian(&parameters, &event, &mut reference_g, &mut reference_h);
+        assert!(close(&h, &reference_h), "{:?} {:?}", h, reference_h);
+        assert_eq!(shape.log_normalization(&parameters, &mut g), 0.0);
+
+        let data = gaussian_sample(5000, 0.3, 1.7);
+        let nll = NegativeLogLikelihood::new(&shape, &data);
+        let result = Minimizer::new(&nll, nll.parameters()).fit();
+        assert_eq!(result.status, FitStatus::Converged);
+        assert!((result.values[1] - 1.7).abs() < 0.1, "{:?}", result);
+    }
+
+    #[test]
+    fn elementary_functions() {
+        check_generated(
+            |[a, b], x| {
+                let r = (x - a).hypot(b);
+                let angle = b.atan2(x).cosh() + (x * a).sinh().atan();
+                let bounded = (x * b).exp_m1().abs().min(3.0) + r.log10().abs().max(0.2);
+                (angle + b.ln_1p() + bounded + (a * x).tanh()).sqrt() / (1.0 + r)
+            },
+            elementary::_value_and_gradient,
+            elementary::_likelihood,
+            elementary::_likelihood_hessian,
+            [0.4, 0.9],
+            &[-1.3, 0.6, 2.2],
+        );
+    }
+
+    #[test]
+    fn special_functions() {
+        use intermediate_representation::special::*;
+        check_generated(
+            |[kappa, mu], x| {
+                let von_mises =
+                    (kappa * (x - mu).cos()).exp() / (2.0 * Float::PI * bessel_i0(kappa));
+                let tail = erfc(mu * x) * gamma_q(2.5, kappa * x * x) + bessel_i1(kappa) + erf(mu);
+                von_mises * tail * (ln_gamma(kappa + 1.0) - digamma(kappa)).exp()
+            },
+            special_terms::_value_and_gradient,
+            special_terms::_likelihood,
+            special_terms::_likelihood_hessian,
+            [1.7, 0.4],
+            &[-2.1, 0.3, 1.2],
+        );
+    }
+
+    #[test]
+    fn piecewise_functions() {
+        check_generated(
+            |[a, b], x| {
+                let core = if x > a {
+                    (x - a).sqrt()
+                } else if x < a - 1.0 {
+                    b * (a - x).ln()
+                } else {
+                    b * (a - x)
+                };
+                let scale = if x < -2.0 {
+                    b * b
+                } else if (0.0..=1.0).contains(&x) || x == 3.0 {
+                    a.exp()
+                } else if x > 2.5 && x <= 4.0 && x != 2.7 {
+                    (x - a).abs()
+                } else {
+                    1.0 + b
+                };
+                (1.0 + core * core) * scale
+            },
+            piecewise::_value_and_gradient,
+            piecewise::_likelihood,
+            piecewise::_likelihood_hessian,
+            [0.5, 0.8],
+            // Every branch is taken at some point, with the untaken ones not
+            // finite at most of them.
+            &[-3.0, -1.5, 0.2, 0.7, 2.0, 2.8, 3.0, 4.5],
+        );
+    }
+
+    #[test]
+    fn inlined_helpers() {
+        let parameters = [0.3, 1.7];
+        for x in [-1.2, 0.4, 2.9] {
+            let (value, gradient) = inlined::_value_and_gradient(parameters, [x]);
+            let (expected, expected_gradient) =
+                normalized_shape::_value_and_gradient(parameters, [x]);
+            assert!((value - expected).abs() < 1e-15);
+            for (g, e) in gradient.iter().zip(expected_gradient) {
+                assert!((g - e).abs() < 1e-15);
+            }
+        }
+
+        // The inlined body is simple enough for the integration pass.
+        let (high, _) = inlined::_antiderivative(parameters, [2.5]);
+        let (low, _) = inlined::_antiderivative(parameters, [-1.0]);
+        let probability = normal_cdf((2.5 - 0.3) / 1.7) - normal_cdf((-1.0 - 0.3) / 1.7);
+        let expected = 1.7 * (2.0 * Float::PI).sqrt() * probability;
+        assert!((high - low - expected).abs() < 1e-12);
+    }
+
+    fn parameter(name: &str) -> Parameter {
+        Parameter::new(name.to_string(), false)
+    }
+
+    fn observable(low: Float, high: Float) -> Data {
+        Data::new("x", Distribution::Uniform(low, high))
+    }
+
+    /// Normalized log-density of a one-dimensional model and its gradient.
+    fn log_density(model: &dyn Model, parameters: &[Float], x: Float) -> (Float, Vec<Float>) {
+        let mut gradient = vec![0.0; parameters.len()];
+        let mut normalization_gradient = vec![0.0; parameters.len()];
+        let value = model.log_likelihood(parameters, &[x], &mut gradient)
+            - model.log_normalization(parameters, &mut normalization_gradient);
+        gradient
+            .iter_mut()
+            .zip(normalization_gradient)
+            .for_each(|(g, n)| *g -= n);
+        (value, gradient)
+    }
+
+    /// Compares the normalized density with reference values and its gradient
+    /// with central differences.
+    fn check_normalized(model: &dyn Model, parameters: &[Float], references: &[(Float, Float)]) {
+        for &(x, reference) in references {
+            let (value, gradient) = log_density(model, parameters, x);
+            assert!(
+                (value.exp() - reference).abs() < 1e-8 * reference,
+                "{} != {} at {}",
+                value.exp(),
+                reference,
+                x
+            );
+            for (i, derivative) in gradient.iter().enumerate() {
+                let step = 1e-5 * parameters[i].abs().max(1.0);
+                let mut shifted = parameters.to_vec();
+                shifted[i] += step;
+                let forward = log_density(model, &shifted, x).0;
+                shifted[i] -= 2.0 * step;
+                let backward = log_density(model, &shifted, x).0;
+                let numeric = (forward - backward) / (2.0 * step);
+                assert!(
+                    (derivative - numeric).abs() < 1e-5 * numeric.abs().max(1.0),
+                    "parameter {} at {}: {} != {}",
+                    i,
+                    x,
+                    derivative,
+                    numeric
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn peak_shapes() {
+        let gaussian_model = gaussian_peak::GaussianPeak {
+            mu: parameter("mu"),
+            sigma: parameter("sigma"),
+            x: observable(-2.0, 3.0),
+        };
+        check_normalized(
+            &gaussian_model,
+            &[0.3, 1.2],
+            &[(0.5, 0.34147937525824), (-1.7, 0.08633950721595074)],
+        );
+
+        let crystal_ball_model = crystal_ball_peak::CrystalBallPeak {
+            mu: parameter("mu"),
+            sigma: parameter("sigma"),
+            alpha: parameter("alpha"),
+            n: parameter("n"),
+            x: observable(-6.0, 3.0),
+        };
+        let parameters = [0.1, 0.8, 1.3, 3.5];
+        check_normalized(
+            &crystal_ball_model,
+            &parameters,
+            &[(-3.0, 0.018960520838214374), (0.4, 0.4309399505938049)],
+        );
+        check_generated(
+            |[mu, sigma, alpha, n], x| crystal_ball(x, mu, sigma, alpha, n),
+            crystal_ball_peak::_value_and_gradient,
+            crystal_ball_peak::_likelihood,
+            crystal_ball_peak::_likelihood_hessian,
+            parameters,
+            &[-3.0, 0.4],
+        );
+
+        let double_sided_model = double_sided_peak::DoubleSidedPeak {
+            mu: parameter("mu"),
+            sigma: parameter("sigma"),
+            alpha_low: parameter("alpha_low"),
+            n_low: parameter("n_low"),
+            alpha_high: parameter("alpha_high"),
+            n_high: parameter("n_high"),
+            x: observable(-7.0, 8.0),
+        };
+        check_normalized(
+            &double_sided_model,
+            &[0.0, 1.0, 1.2, 2.5, 1.8, 4.0],
+            &[
+                (-3.0, 0.0359252994813098),
+                (0.5, 0.30897289632170366),
+                (4.0, 0.004418123259226855),
+            ],
+        );
+
+        let breit_wigner_model = breit_wigner_peak::BreitWignerPeak {
+            mean: parameter("mean"),
+            width: parameter("width"),
+            x: observable(-1.0, 4.0),
+        };
+        check_normalized(
+            &breit_wigner_model,
+            &[1.0, 0.6],
+            &[(1.2, 0.7976720166529392), (3.0, 0.02535387827992227)],
+        );
+
+        let voigtian_model = voigtian_peak::VoigtianPeak {
+            mean: parameter("mean"),
+            width: parameter("width"),
+            sigma: parameter("sigma"),
+            x: Data::new("x", Distribution::Gaussian(0.0, 1.0, None)),
+        };
+        let parameters = [0.2, 0.5, 0.4];
+        check_normalized(
+            &voigtian_model,
+            &parameters,
+            &[(0.0, 0.59723252871605), (1.5, 0.06618740366928604)],
+        );
+        check_generated(
+            |[mean, width, sigma], x| voigtian(x, mean, width, sigma),
+            voigtian_peak::_value_and_gradient,
+            voigtian_peak::_likelihood,
+            voigtian_peak::_likelihood_hessian,
+            parameters,
+            &[0.0, 1.5],
+        );
+    }
+
+    #[test]
+    fn background_shapes() {
+        let argus_model = argus_background::ArgusBackground {
+            endpoint: parameter("endpoint"),
+            curvature: parameter("curvature"),
+            power: parameter("power"),
+            x: observable(5.2, 5.27),
+        };
+        let parameters = [5.29, -8.0, 0.5];
+        check_normalized(
+            &argus_model,
+            &parameters,
+            &[(5.24, 14.25145586170031), (5.26, 11.778061916120676)],
+        );
+        check_generated(
+            |[endpoint, curvature, power], x| argus(x, endpoint, curvature, power),
+            argus_background::_value_and_gradient,
+            argus_background::_likelihood,
+            argus_background::_likelihood_hessian,
+            parameters,
+            &[5.24, 5.26],
+        );
+
+        let johnson_model = johnson_background::JohnsonBackground {
+            mu: parameter("mu"),
+            lambda: parameter("lambda"),
+            gamma: parameter("gamma"),
+            delta: parameter("delta"),
+            x: observable(-4.0, 6.0),
+        };
+        let parameters = [0.5, 1.5, -0.4, 1.7];
+        check_normalized(
+            &johnson_model,
+            &parameters,
+            &[(-1.0, 0.052830165238591284), (2.0, 0.17516885899818713)],
+        );
+        check_generated(
+            |[mu, lambda, gamma, delta], x| johnson_su(x, mu, lambda, gamma, delta),
+            johnson_background::_value_and_gradient,
+            johnson_background::_likelihood,
+            johnson_background::_likelihood_hessian,
+            parameters,
+            &[-1.0, 2.0],
+        );
+
+        let chebyshev_model = chebyshev_background::ChebyshevBackground {
+            c1: parameter("c1"),
+            c2: parameter("c2"),
+            c3: parameter("c3"),
+            x: observable(0.0, 10.0),
+        };
+        check_normalized(
+            &chebyshev_model,
+            &[0.3, -0.2, 0.05],
+            &[(2.5, 0.09375), (7.0, 0.113325)],
+        );
+
+        let bernstein_model = bernstein_background::BernsteinBackground {
+            b0: parameter("b0"),
+            b1: parameter("b1"),
+            b2: parameter("b2"),
+            b3: parameter("b3"),
+            x: observable(1.0, 3.0),
+        };
+        check_normalized(
+            &bernstein_model,
+            &[1.0, 0.5, 2.0, 1.5],
+            &[(1.4, 0.3632), (2.7, 0.6414)],
+        );
     }
 }